@@ -0,0 +1,59 @@
+//! Propagates and draws an asteroid belt of 10k objects every frame.
+//!
+//! Run with `cargo run --release --example asteroid_belt --features rayon`
+//! to compare against the single threaded propagation.
+
+use std::{convert::TryInto as _, f64::consts::TAU};
+
+use ::orbits::*;
+use macroquad::{miniquad::window::screen_size, prelude::*, rand::gen_range};
+
+const OBJECTS: usize = 10_000;
+
+#[macroquad::main("asteroid belt")]
+async fn main() {
+    let mut orbits = orbits::Orbits::default();
+    for _ in 0..OBJECTS {
+        orbits.insert(orbits::Object {
+            angle: gen_range(0.0, TAU).try_into().unwrap(),
+            t: gen_range(0.0, 100_000.0).into(),
+            orbit: Orbit {
+                p: gen_range(150.0, 300.0).try_into().unwrap(),
+                epsilon: gen_range(0.0, 0.2).try_into().unwrap(),
+            },
+        });
+    }
+
+    let mut positions = Vec::with_capacity(OBJECTS);
+    let mut t = 0.0;
+    // Smoothed propagation time in milliseconds.
+    let mut propagation = 0.0;
+
+    loop {
+        let s = Vec2::from(screen_size()) / 2.;
+        clear_background(BLACK);
+        draw_circle(s.x, s.y, 50., YELLOW);
+
+        let start = get_time();
+        orbits.positions(t, &mut positions);
+        propagation = propagation * 0.95 + (get_time() - start) * 1000.0 * 0.05;
+
+        for &(x, y) in &positions {
+            draw_rectangle(x + s.x, y + s.y, 1.0, 1.0, GRAY);
+        }
+
+        draw_text(
+            &format!(
+                "{OBJECTS} objects, propagation: {propagation:.2}ms, fps: {}",
+                get_fps()
+            ),
+            10.0,
+            20.0,
+            20.0,
+            WHITE,
+        );
+
+        t += 10.0;
+        next_frame().await;
+    }
+}
@@ -1,62 +1,382 @@
+//! Sandbox for authoring scenes of orbits.
+//!
+//! Run with `cargo run --example solar_playground -- path/to/scene.orbits`.
+//! Without a path, `scene.orbits` in the current directory is used.
+//! See `HELP` below or the bottom of the window for the controls.
+
 use std::convert::TryInto as _;
 
 use ::orbits::*;
 use macroquad::{miniquad::window::screen_size, prelude::*};
 
-#[macroquad::main("solar playground")]
-async fn main() {
-    prevent_quit();
+const HELP: &str = "drag: new orbit | click: select | space: play/pause | left/right: scrub | \
+                    up/down: speed | wheel: zoom | delete: remove | c/p: make circular/parabolic | \
+                    s/l: save/load";
+
+/// Screen pixels per unit of velocity when drawing and dragging velocities.
+const VELOCITY_SCALE: f64 = 1000.0;
+/// How far away from an object or handle, in pixels, a click may be to still grab it.
+const GRAB_RADIUS: f32 = 10.0;
+const TIMELINE_HEIGHT: f32 = 20.0;
 
-    let mut orbits = orbits::Orbits::default();
-    for i in 0..10 {
-        orbits.insert(Orbit::from_pos_dir(
-            100.0.try_into().unwrap(),
-            1.0.try_into().unwrap(),
-            (i as f64 / 100.).try_into().unwrap(),
-            0.1.try_into().unwrap(),
-        ));
+enum Drag {
+    /// Dragging out the velocity of a new object from the given world position.
+    Create {
+        start: (f64, f64),
+        id: Option<usize>,
+    },
+    /// Dragging the velocity handle of the selected object.
+    Velocity,
+    /// Dragging along the timeline.
+    Scrub,
+}
+
+struct Sandbox {
+    orbits: orbits::Orbits,
+    t: Time,
+    playing: bool,
+    /// Time units per second.
+    speed: f64,
+    /// Screen pixels per world unit.
+    zoom: f32,
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    path: String,
+    /// Result of the last save or load.
+    status: String,
+}
+
+impl Sandbox {
+    fn center(&self) -> Vec2 {
+        Vec2::from(screen_size()) / 2.
     }
-    let mut mouse_down = None;
-    let mut last_orbit = None;
 
-    while !is_quit_requested() || is_key_pressed(KeyCode::Escape) {
-        let s = Vec2::from(screen_size()) / 2.;
-        draw_circle(s.x, s.y, 50., YELLOW);
-
-        if is_mouse_button_down(MouseButton::Left) {
-            let pos = Vec2::from(mouse_position());
-            let mouse_down = *mouse_down.get_or_insert(pos);
-            draw_circle(mouse_down.x, mouse_down.y, 10., RED);
-            if pos.distance_squared(mouse_down) > 50. {
-                if let Some(last_orbit) = last_orbit.take() {
-                    orbits.remove(last_orbit);
+    fn to_screen(&self, (x, y): (f64, f64)) -> Vec2 {
+        self.center() + vec2(x as f32, y as f32) * self.zoom
+    }
+
+    fn to_world(&self, pos: Vec2) -> (f64, f64) {
+        let pos = ((pos - self.center()) / self.zoom).as_dvec2();
+        (pos.x, pos.y)
+    }
+
+    /// Create an object that is at `pos` with velocity `vel` right now.
+    /// Returns `None` if the object would fall straight into the center of gravity.
+    fn object(&self, (x, y): (f64, f64), (dx, dy): (f64, f64)) -> Option<orbits::Object> {
+        if (x * dy - y * dx).abs() < 1e-9 {
+            return None;
+        }
+        let mut object = Orbit::from_pos_dir(
+            x.try_into().ok()?,
+            y.try_into().ok()?,
+            dx.try_into().ok()?,
+            dy.try_into().ok()?,
+        );
+        object.t += -self.t;
+        Some(object)
+    }
+
+    /// Replace the object `id` (if any) by `object`, returning the new id.
+    fn replace(&mut self, id: Option<usize>, object: orbits::Object) -> usize {
+        if let Some(id) = id {
+            self.orbits.remove(id);
+        }
+        let id = self.orbits.insert(object);
+        if self.selected.is_some() {
+            self.selected = Some(id);
+        }
+        id
+    }
+
+    /// Change the velocity of the selected object, keeping its current position.
+    fn set_velocity(&mut self, f: impl FnOnce((f64, f64), (f64, f64)) -> (f64, f64)) {
+        let Some(object) = self.selected.and_then(|id| self.orbits.get(id)) else {
+            return;
+        };
+        let pos = object.position_at(self.t);
+        let vel = f(pos, object.velocity_at(self.t));
+        if let Some(object) = self.object(pos, vel) {
+            self.replace(self.selected, object);
+        }
+    }
+
+    fn timeline(&self) -> Rect {
+        let (w, h) = screen_size();
+        Rect::new(0., h - TIMELINE_HEIGHT, w, TIMELINE_HEIGHT)
+    }
+
+    /// The timeline spans two of the longest periods, or a fixed time if there are no closed orbits.
+    fn timeline_span(&self) -> f64 {
+        self.orbits
+            .iter()
+            .filter_map(|(_, object)| object.period())
+            .fold(10_000.0, |a: f64, b| a.max(b * 2.))
+    }
+
+    fn save(&mut self) {
+        self.status = match std::fs::write(&self.path, self.orbits.to_string()) {
+            Ok(()) => format!("saved {}", self.path),
+            Err(err) => format!("could not save {}: {}", self.path, err),
+        };
+    }
+
+    fn load(&mut self) {
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|s| s.parse::<orbits::Orbits>().map_err(|err| err.to_string()));
+        self.status = match result {
+            Ok(orbits) => {
+                self.orbits = orbits;
+                self.selected = None;
+                self.t = Time::ZERO;
+                format!("loaded {}", self.path)
+            }
+            Err(err) => format!("could not load {}: {}", self.path, err),
+        };
+    }
+
+    fn update(&mut self) {
+        let dt = get_frame_time() as f64;
+        if is_key_pressed(KeyCode::Space) {
+            self.playing = !self.playing;
+        }
+        if self.playing {
+            self.t += self.speed * dt;
+        }
+        if is_key_down(KeyCode::Right) {
+            self.t += self.speed * dt * 5.;
+        }
+        if is_key_down(KeyCode::Left) {
+            self.t -= self.speed * dt * 5.;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.speed *= 2.;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.speed /= 2.;
+        }
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0. {
+            self.zoom *= 1.1_f32.powf(wheel.signum());
+        }
+        if is_key_pressed(KeyCode::Delete) || is_key_pressed(KeyCode::Backspace) {
+            if let Some(id) = self.selected.take() {
+                self.orbits.remove(id);
+            }
+        }
+        if is_key_pressed(KeyCode::C) {
+            self.set_velocity(|(x, y), (dx, dy)| {
+                let r = x.hypot(y);
+                let speed = (1. / r).sqrt();
+                // Keep the direction of travel around the center of gravity.
+                let sign = (x * dy - y * dx).signum();
+                (-y / r * speed * sign, x / r * speed * sign)
+            });
+        }
+        if is_key_pressed(KeyCode::P) {
+            self.set_velocity(|(x, y), (dx, dy)| {
+                // Escape velocity, keeping the direction.
+                let speed = (2. / x.hypot(y)).sqrt() / dx.hypot(dy);
+                (dx * speed, dy * speed)
+            });
+        }
+        if is_key_pressed(KeyCode::S) {
+            self.save();
+        }
+        if is_key_pressed(KeyCode::L) {
+            self.load();
+        }
+        self.mouse();
+    }
+
+    fn mouse(&mut self) {
+        let mouse = Vec2::from(mouse_position());
+        if is_mouse_button_pressed(MouseButton::Left) {
+            self.drag = self.grab(mouse);
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.drag = None;
+            return;
+        }
+        match self.drag.take() {
+            Some(Drag::Create { start, id }) => {
+                let start_screen = self.to_screen(start);
+                let mut id = id;
+                if mouse.distance_squared(start_screen) > 50. {
+                    let d = (mouse - start_screen).as_dvec2() / VELOCITY_SCALE;
+                    if let Some(object) = self.object(start, (d.x, d.y)) {
+                        id = Some(self.replace(id, object));
+                    }
                 }
-                let d = (pos - mouse_down).as_dvec2() / 1000.;
-                let mouse_down = (mouse_down - s).as_dvec2();
-                last_orbit = Some(orbits.insert(Orbit::from_pos_dir(
-                    mouse_down.x.try_into().unwrap(),
-                    mouse_down.y.try_into().unwrap(),
-                    d.x.try_into().unwrap(),
-                    d.y.try_into().unwrap(),
-                )));
+                self.drag = Some(Drag::Create { start, id });
+            }
+            Some(Drag::Velocity) => {
+                let mouse = self.to_world(mouse);
+                let scale = VELOCITY_SCALE / self.zoom as f64;
+                self.set_velocity(|(x, y), _| ((mouse.0 - x) / scale, (mouse.1 - y) / scale));
+                self.drag = Some(Drag::Velocity);
+            }
+            Some(Drag::Scrub) => {
+                let timeline = self.timeline();
+                let frac = ((mouse.x - timeline.x) / timeline.w).clamp(0., 1.);
+                self.t = Time::from(frac as f64 * self.timeline_span());
+                self.drag = Some(Drag::Scrub);
             }
-        } else {
-            mouse_down = None;
-            last_orbit = None;
+            None => {}
         }
+    }
 
-        for (_, (_x, _y), mut points) in orbits.draw(0.0, 300) {
-            let start = points.next().unwrap();
-            let (mut x, mut y) = start;
+    /// Decide what a click at `mouse` starts dragging. Clicking an object only selects it.
+    fn grab(&mut self, mouse: Vec2) -> Option<Drag> {
+        if self.timeline().contains(mouse) {
+            return Some(Drag::Scrub);
+        }
+        if let Some(handle) = self.handle() {
+            if handle.distance(mouse) < GRAB_RADIUS {
+                return Some(Drag::Velocity);
+            }
+        }
+        let radius = (GRAB_RADIUS / self.zoom) as f64;
+        let index = self.orbits.spatial_index(self.t, radius);
+        self.selected = index
+            .nearest(self.to_world(mouse))
+            .filter(|&(_, distance)| distance <= radius)
+            .map(|(id, _)| id);
+        match self.selected {
+            Some(_) => None,
+            None => Some(Drag::Create {
+                start: self.to_world(mouse),
+                id: None,
+            }),
+        }
+    }
 
-            for (new_x, new_y) in points {
-                draw_line(x + s.x, y + s.y, new_x + s.x, new_y + s.y, 1., WHITE);
-                x = new_x;
-                y = new_y;
+    /// Screen position of the tip of the velocity arrow of the selected object.
+    fn handle(&self) -> Option<Vec2> {
+        let object = self.orbits.get(self.selected?)?;
+        let (x, y) = object.position_at(self.t);
+        let (dx, dy) = object.velocity_at(self.t);
+        let scale = VELOCITY_SCALE / self.zoom as f64;
+        Some(self.to_screen((x + dx * scale, y + dy * scale)))
+    }
+
+    fn draw(&self) {
+        let center = self.center();
+        draw_circle(center.x, center.y, 50. * self.zoom, YELLOW);
+
+        let tessellation = Tessellation::Tolerance {
+            max_error: 0.5 / self.zoom as f64,
+            max_radius: (center.length() / self.zoom) as f64,
+        };
+        for (kind, pos, mut points) in self.orbits.draw(self.t, tessellation) {
+            let color = match kind {
+                OrbitKind::Circle => WHITE,
+                OrbitKind::Ellipse => GRAY,
+                OrbitKind::Parabola => GREEN,
+                OrbitKind::Hyperbola => RED,
+            };
+            let point = |(x, y): (f32, f32)| self.to_screen((x.into(), y.into()));
+            let mut prev = point(points.next().unwrap());
+            for next in points.map(point) {
+                draw_line(prev.x, prev.y, next.x, next.y, 1., color);
+                prev = next;
+            }
+            let pos = point(pos);
+            draw_circle(pos.x, pos.y, 3., color);
+        }
+
+        if let (Some(object), Some(handle)) = (
+            self.selected.and_then(|id| self.orbits.get(id)),
+            self.handle(),
+        ) {
+            let pos = self.to_screen(object.position_at(self.t));
+            draw_circle_lines(pos.x, pos.y, GRAB_RADIUS, 1., YELLOW);
+            draw_line(pos.x, pos.y, handle.x, handle.y, 1., YELLOW);
+            draw_circle(handle.x, handle.y, 4., YELLOW);
+            for (i, line) in self.elements(object).iter().enumerate() {
+                draw_text(line, 10., 20. + i as f32 * 18., 18., WHITE);
             }
-            draw_line(start.0 + s.x, start.1 + s.y, s.x + x, s.y + y, 1., WHITE);
         }
 
+        let timeline = self.timeline();
+        draw_rectangle(timeline.x, timeline.y, timeline.w, timeline.h, DARKGRAY);
+        let frac = (self.t.as_f64() / self.timeline_span()).clamp(0., 1.) as f32;
+        let x = timeline.x + frac * timeline.w;
+        draw_line(x, timeline.y, x, timeline.y + timeline.h, 2., WHITE);
+        let (w, _) = screen_size();
+        let info = format!(
+            "t = {:.1} {} x{}",
+            self.t.as_f64(),
+            if self.playing { "playing" } else { "paused" },
+            self.speed
+        );
+        draw_text(&info, w - 300., 20., 18., WHITE);
+        draw_text(&self.status, w - 300., 38., 18., WHITE);
+        draw_text(HELP, 10., timeline.y - 8., 16., GRAY);
+    }
+
+    /// Human readable orbital elements of `object`.
+    fn elements(&self, object: &orbits::Object) -> Vec<String> {
+        let orbit = &object.orbit;
+        let (x, y) = object.position_at(self.t);
+        let (dx, dy) = object.velocity_at(self.t);
+        let mut lines = vec![
+            format!("{:?}", orbit.kind()),
+            format!("p = {:.3}", f64::from(orbit.p)),
+            format!("e = {:.6}", f64::from(orbit.epsilon)),
+            format!(
+                "periapsis angle = {:.1}°",
+                f64::from(object.angle).to_degrees()
+            ),
+            format!("periapsis = {:.3}", f64::from(orbit.perihelion())),
+        ];
+        if let Some(period) = object.period() {
+            lines.push(format!("apoapsis = {:.3}", f64::from(orbit.aphelion())));
+            lines.push(format!(
+                "semi-major axis = {:.3}",
+                f64::from(orbit.semi_major())
+            ));
+            lines.push(format!("period = {period:.1}"));
+        }
+        lines.push(format!("position = ({x:.2}, {y:.2})"));
+        lines.push(format!("velocity = ({dx:.5}, {dy:.5})"));
+        lines
+    }
+}
+
+#[macroquad::main("solar playground")]
+async fn main() {
+    prevent_quit();
+
+    let mut sandbox = Sandbox {
+        orbits: Default::default(),
+        t: Time::ZERO,
+        playing: false,
+        speed: 100.,
+        zoom: 1.,
+        selected: None,
+        drag: None,
+        path: std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| "scene.orbits".to_owned()),
+        status: String::new(),
+    };
+    if std::path::Path::new(&sandbox.path).exists() {
+        sandbox.load();
+    } else {
+        for i in 0..10 {
+            sandbox.orbits.insert(Orbit::from_pos_dir(
+                100.0.try_into().unwrap(),
+                1.0.try_into().unwrap(),
+                (i as f64 / 100.).try_into().unwrap(),
+                0.1.try_into().unwrap(),
+            ));
+        }
+    }
+
+    while !is_quit_requested() || is_key_pressed(KeyCode::Escape) {
+        sandbox.update();
+        sandbox.draw();
         next_frame().await;
     }
 }
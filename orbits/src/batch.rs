@@ -0,0 +1,224 @@
+//! Propagation of many objects at once.
+//!
+//! [Object::angle_at] recomputes the semi major axis, mean motion and period of the orbit
+//! every time it is called and checks every intermediate float for NaNs. That is fine for
+//! a handful of objects, but for asteroid belts with thousands of bodies it dominates the frame.
+//! A [Batch] stores the elements of all objects as a structure of arrays, together with the
+//! quantities derived from them, so that propagation is a tight loop over plain [f64]s.
+//!
+//! Enable the `rayon` feature to propagate in parallel.
+
+use alloc::vec::Vec;
+use core::f64::consts::TAU;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{math, orbits::Object, time::Time, OrbitKind};
+
+/// Orbital elements and cached derived quantities of many objects, stored as a structure of arrays.
+/// Indices are the same as the order in which objects were [pushed](Batch::push).
+#[derive(Default, Debug, Clone)]
+pub struct Batch {
+    /// Angle of apehelion.
+    angle: Vec<f64>,
+    /// Starting point of object in the orbit.
+    t: Vec<Time>,
+    /// Semi-latus rectum.
+    p: Vec<f64>,
+    /// Eccentricity.
+    epsilon: Vec<f64>,
+    kind: Vec<OrbitKind>,
+    mean_motion: Vec<f64>,
+    /// Orbital period, infinite for open orbits.
+    period: Vec<f64>,
+    /// `sqrt(|1 - e²|)`, used for converting the eccentric anomaly to the true anomaly.
+    eps_root: Vec<f64>,
+}
+
+impl Batch {
+    pub fn len(&self) -> usize {
+        self.angle.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.angle.is_empty()
+    }
+
+    /// Add an object at the end, computing all its derived quantities once.
+    pub fn push(&mut self, object: &Object) {
+        let orbit = &object.orbit;
+        let kind = orbit.kind();
+        let mean_motion = f64::from(orbit.mean_motion());
+        self.angle.push(object.angle.into());
+        self.t.push(object.t);
+        self.p.push(orbit.p.into());
+        self.epsilon.push(orbit.epsilon.into());
+        self.kind.push(kind);
+        self.mean_motion.push(mean_motion);
+        self.period.push(match kind {
+            OrbitKind::Circle | OrbitKind::Ellipse => TAU / mean_motion,
+            OrbitKind::Parabola | OrbitKind::Hyperbola => f64::INFINITY,
+        });
+        let e = f64::from(orbit.epsilon);
+        self.eps_root.push(math::sqrt((1.0 - e * e).abs()));
+    }
+
+    /// Remove the object at index `idx`, shifting all later objects down by one.
+    pub fn remove(&mut self, idx: usize) {
+        self.angle.remove(idx);
+        self.t.remove(idx);
+        self.p.remove(idx);
+        self.epsilon.remove(idx);
+        self.kind.remove(idx);
+        self.mean_motion.remove(idx);
+        self.period.remove(idx);
+        self.eps_root.remove(idx);
+    }
+
+    pub(crate) fn set_t(&mut self, idx: usize, t: Time) {
+        self.t[idx] = t;
+    }
+
+    /// Orbital period of the object at `idx`. Infinite for open orbits.
+    pub fn period(&self, idx: usize) -> f64 {
+        self.period[idx]
+    }
+
+    /// Angle of the object at `idx` in its orbit at time `t`.
+    pub fn angle_at(&self, idx: usize, t: impl Into<Time>) -> f64 {
+        let time = t.into() + self.t[idx];
+        let e = self.epsilon[idx];
+        match self.kind[idx] {
+            OrbitKind::Circle => {
+                let period = self.period[idx];
+                TAU * time.rem_euclid(period) / period
+            }
+            OrbitKind::Ellipse => {
+                let big_e = eccentric_anomaly(
+                    OrbitKind::Ellipse,
+                    e,
+                    self.mean_motion[idx] * time.rem_euclid(self.period[idx]),
+                );
+                let (sin, cos) = math::sin_cos(big_e);
+                math::atan2(sin * self.eps_root[idx], cos - e)
+            }
+            // Open orbits are symmetric around their apehelion, see `Object::angle_at`.
+            OrbitKind::Parabola => {
+                let time = time.as_f64();
+                let u =
+                    eccentric_anomaly(OrbitKind::Parabola, e, self.mean_motion[idx] * time.abs());
+                let u2 = u * u;
+                math::acos((1.0 - u2) / (1.0 + u2)) * time.signum()
+            }
+            OrbitKind::Hyperbola => {
+                let time = time.as_f64();
+                let big_e =
+                    eccentric_anomaly(OrbitKind::Hyperbola, e, self.mean_motion[idx] * time.abs());
+                let cosh = math::cosh(big_e);
+                math::acos((e - cosh) / (e * cosh - 1.0)) * time.signum()
+            }
+        }
+    }
+
+    /// Position of the object at `idx` at time `t`, relative to the center of gravity.
+    pub fn position(&self, idx: usize, t: impl Into<Time>) -> (f64, f64) {
+        let angle = self.angle_at(idx, t);
+        let r = self.p[idx] / (1.0 + self.epsilon[idx] * math::cos(angle));
+        let (y, x) = math::sin_cos(angle + self.angle[idx]);
+        (x * r, y * r)
+    }
+
+    /// Compute the positions of all objects at time `t`, replacing the contents of `positions`.
+    /// Reuse the `positions` vector across frames to avoid reallocating it.
+    pub fn propagate(&self, t: impl Into<Time>, positions: &mut Vec<(f32, f32)>) {
+        let t = t.into();
+        positions.clear();
+        positions.resize(self.len(), (0.0, 0.0));
+        let position = |(idx, pos): (usize, &mut (f32, f32))| {
+            let (x, y) = self.position(idx, t);
+            *pos = (x as f32, y as f32);
+        };
+        #[cfg(feature = "rayon")]
+        positions.par_iter_mut().enumerate().for_each(position);
+        #[cfg(not(feature = "rayon"))]
+        positions.iter_mut().enumerate().for_each(position);
+    }
+}
+
+/// Same iteration as [crate::Orbit::eccentric_anomaly], but on plain floats and with
+/// the mean anomaly already computed.
+fn eccentric_anomaly(kind: OrbitKind, epsilon: f64, mean_anomaly: f64) -> f64 {
+    let mut e = match kind {
+        OrbitKind::Hyperbola => math::asinh(mean_anomaly / epsilon),
+        _ => mean_anomaly,
+    };
+    for _ in 0..=30 {
+        let old = e;
+        e = match kind {
+            OrbitKind::Circle => unreachable!(),
+            OrbitKind::Ellipse => {
+                let (sin, cos) = math::sin_cos(e);
+                (mean_anomaly - epsilon * (e * cos - sin)) / (1.0 - epsilon * cos)
+            }
+            OrbitKind::Parabola => {
+                let u2 = e * e;
+                let c = mean_anomaly * math::sqrt(4.5);
+                (2.0 * u2 * e + c) / (3.0 + 3.0 * u2)
+            }
+            OrbitKind::Hyperbola => {
+                let cosh = math::cosh(e);
+                let sinh = math::sinh(e);
+                (mean_anomaly + epsilon * (e * cosh - sinh)) / (epsilon * cosh - 1.0)
+            }
+        };
+        if (e - old).abs() < 1e-6 {
+            break;
+        }
+    }
+    e
+}
+
+#[test]
+fn matches_single_object_propagation() {
+    use crate::Orbit;
+    use core::convert::TryInto as _;
+
+    let objects = [
+        Orbit::from_pos_dir(
+            100.0.try_into().unwrap(),
+            0.0.try_into().unwrap(),
+            0.0.try_into().unwrap(),
+            0.12.try_into().unwrap(),
+        ),
+        Orbit::from_pos_dir(
+            100.0.try_into().unwrap(),
+            1.0.try_into().unwrap(),
+            0.05.try_into().unwrap(),
+            0.1.try_into().unwrap(),
+        ),
+        Object {
+            angle: 1.0.try_into().unwrap(),
+            t: Time::ZERO,
+            orbit: Orbit::circular(200.0.try_into().unwrap()),
+        },
+    ];
+    let mut batch = Batch::default();
+    for object in &objects {
+        batch.push(object);
+    }
+    for t in [0.0, 10.0, 1234.5, 100_000.0] {
+        for (idx, object) in objects.iter().enumerate() {
+            let expected = f64::from(object.angle_at(t));
+            let actual = batch.angle_at(idx, t);
+            assert!(
+                (expected - actual).abs() < 1e-9,
+                "{} at {}: {} != {}",
+                idx,
+                t,
+                expected,
+                actual
+            );
+        }
+    }
+}
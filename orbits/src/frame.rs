@@ -0,0 +1,170 @@
+//! Reference frames for looking at orbits from somewhere other than the center of gravity.
+//!
+//! All positions computed by this crate are relative to the center of gravity with fixed axes.
+//! A [Frame] describes a different point of view, e.g. following an object or rotating with it.
+//! Frames can move, so [Orbits::transform] turns a frame into a [Transform] for one point in time,
+//! which then converts positions, directions and tessellated orbits into that frame.
+
+use crate::{math, time::Time, Orbits};
+
+/// The point that is at `(0, 0)` in a [Frame].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    /// The center of gravity.
+    Body,
+    /// The current position of an object.
+    Object(usize),
+    /// A fixed point.
+    Point((f64, f64)),
+}
+
+/// Where the x axis of a [Frame] points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    /// The same axes as the center of gravity.
+    Fixed,
+    /// From the center of gravity towards an object, so the center of gravity
+    /// and the object always appear on the same line.
+    Radial(usize),
+    /// Rotating counterclockwise with the given angular velocity, starting out with fixed axes at time 0.
+    Rate(f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub origin: Origin,
+    pub rotation: Rotation,
+}
+
+impl Frame {
+    /// The frame everything in this crate is computed in.
+    pub const INERTIAL: Self = Self {
+        origin: Origin::Body,
+        rotation: Rotation::Fixed,
+    };
+
+    /// Follow object `id` without rotating.
+    pub fn object(id: usize) -> Self {
+        Self {
+            origin: Origin::Object(id),
+            rotation: Rotation::Fixed,
+        }
+    }
+
+    /// Follow object `id`, rotating so the center of gravity is always in negative x direction.
+    pub fn co_rotating(id: usize) -> Self {
+        Self {
+            origin: Origin::Object(id),
+            rotation: Rotation::Radial(id),
+        }
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::INERTIAL
+    }
+}
+
+/// Converts from the coordinates of the center of gravity into a [Frame] at one point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    origin: (f64, f64),
+    /// Cosine and sine of the angle of the x axis of the frame.
+    axis: (f64, f64),
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        origin: (0.0, 0.0),
+        axis: (1.0, 0.0),
+    };
+
+    /// Convert a position into the frame.
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        self.rotate((x - self.origin.0, y - self.origin.1))
+    }
+
+    /// Convert a position in the frame back into the coordinates of the center of gravity.
+    pub fn inverse(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (cos, sin) = self.axis;
+        (
+            x * cos - y * sin + self.origin.0,
+            x * sin + y * cos + self.origin.1,
+        )
+    }
+
+    /// Convert a direction (or velocity, ignoring the motion of the frame itself) into the frame.
+    pub fn rotate(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (cos, sin) = self.axis;
+        (x * cos + y * sin, y * cos - x * sin)
+    }
+
+    /// Convert all points of a tessellated orbit into the frame.
+    pub fn apply_all<'a>(
+        &'a self,
+        points: impl IntoIterator<Item = (f64, f64)> + 'a,
+    ) -> impl Iterator<Item = (f64, f64)> + 'a {
+        points.into_iter().map(move |point| self.apply(point))
+    }
+}
+
+impl Orbits {
+    /// The transform into `frame` at time `t`, or `None` if `frame` refers to an object that doesn't exist.
+    pub fn transform(&self, frame: Frame, t: impl Into<Time>) -> Option<Transform> {
+        let t = t.into();
+        let origin = match frame.origin {
+            Origin::Body => (0.0, 0.0),
+            Origin::Object(id) => self.get(id)?.position_at(t),
+            Origin::Point(point) => point,
+        };
+        let angle = match frame.rotation {
+            Rotation::Fixed => 0.0,
+            Rotation::Radial(id) => {
+                let (x, y) = self.get(id)?.position_at(t);
+                math::atan2(y, x)
+            }
+            // Only the fraction of a turn matters, so use the time within one turn for precision.
+            Rotation::Rate(rate) if rate != 0.0 => {
+                t.rem_euclid(core::f64::consts::TAU / rate.abs()) * rate
+            }
+            Rotation::Rate(_) => 0.0,
+        };
+        let (sin, cos) = math::sin_cos(angle);
+        Some(Transform {
+            origin,
+            axis: (cos, sin),
+        })
+    }
+}
+
+#[test]
+fn co_rotating_frame_keeps_body_behind_object() {
+    use crate::{orbits::Object, Orbit};
+    use core::convert::TryInto as _;
+
+    let mut orbits = Orbits::default();
+    let ship = orbits.insert(Object {
+        angle: 0.0.try_into().unwrap(),
+        t: Time::ZERO,
+        orbit: Orbit::circular(100.0.try_into().unwrap()),
+    });
+    assert!(orbits.transform(Frame::object(ship + 1), 0.0).is_none());
+    for &t in &[0.0, 123.0, 4567.0] {
+        let transform = orbits.transform(Frame::co_rotating(ship), t).unwrap();
+        let ship_pos = orbits.get(ship).unwrap().position_at(t);
+        let (x, y) = transform.apply(ship_pos);
+        assert!(x.abs() < 1e-9 && y.abs() < 1e-9);
+        let (x, y) = transform.apply((0.0, 0.0));
+        assert!((x + 100.0).abs() < 1e-9 && y.abs() < 1e-9);
+        let (x, y) = transform.inverse(transform.apply((3.0, -7.0)));
+        assert!((x - 3.0).abs() < 1e-9 && (y + 7.0).abs() < 1e-9);
+    }
+    let frame = Frame {
+        origin: Origin::Body,
+        rotation: Rotation::Rate(0.001),
+    };
+    let quarter = core::f64::consts::FRAC_PI_2 / 0.001;
+    let (x, y) = orbits.transform(frame, quarter).unwrap().apply((0.0, 1.0));
+    assert!((x - 1.0).abs() < 1e-9 && y.abs() < 1e-9);
+}
@@ -0,0 +1,134 @@
+//! Geometric features of orbits, in the coordinates of the center of gravity.
+//!
+//! These are meant for drawing markers and labels on top of a tessellated orbit,
+//! so everything is returned as plain [f64] pairs.
+
+use core::{convert::TryFrom as _, f64::consts::PI};
+
+use typed_floats::NonNaNFinite;
+
+use crate::{math, orbits::Object, time::Time, OrbitKind};
+
+impl Object {
+    /// Position at angle `angle` in the orbit (the true anomaly), in the coordinates of the center of gravity.
+    pub fn point_at(&self, angle: f64) -> (f64, f64) {
+        let r = f64::from(self.r(NonNaNFinite::try_from(angle).unwrap()));
+        let (y, x) = math::sin_cos(angle + f64::from(self.angle));
+        (x * r, y * r)
+    }
+
+    /// Position of the object at time `t`.
+    pub fn position_at(&self, t: impl Into<Time>) -> (f64, f64) {
+        self.point_at(self.angle_at(t).into())
+    }
+
+    /// Velocity of the object at time `t`.
+    pub fn velocity_at(&self, t: impl Into<Time>) -> (f64, f64) {
+        let angle = f64::from(self.angle_at(t));
+        let e = f64::from(self.orbit.epsilon);
+        // The gravitational parameter is 1, so the specific angular momentum is `sqrt(p)`.
+        let h = math::sqrt(f64::from(self.orbit.p));
+        let radial = e * math::sin(angle) / h;
+        let tangential = (1.0 + e * math::cos(angle)) / h;
+        let (sin, cos) = math::sin_cos(angle + f64::from(self.angle));
+        (
+            radial * cos - tangential * sin,
+            radial * sin + tangential * cos,
+        )
+    }
+
+    /// Unit vector pointing in the direction the object is moving at time `t`.
+    pub fn direction_at(&self, t: impl Into<Time>) -> (f64, f64) {
+        let (x, y) = self.velocity_at(t);
+        let len = math::hypot(x, y);
+        (x / len, y / len)
+    }
+
+    /// The point closest to the center of gravity.
+    pub fn periapsis(&self) -> (f64, f64) {
+        self.point_at(0.0)
+    }
+
+    /// The point farthest away from the center of gravity. Open orbits have none.
+    pub fn apoapsis(&self) -> Option<(f64, f64)> {
+        match self.orbit.kind() {
+            OrbitKind::Circle | OrbitKind::Ellipse => Some(self.point_at(PI)),
+            OrbitKind::Parabola | OrbitKind::Hyperbola => None,
+        }
+    }
+
+    /// Center of the ellipse or hyperbola. For a hyperbola this is where the asymptotes cross.
+    /// Parabolas have no center.
+    pub fn center(&self) -> Option<(f64, f64)> {
+        let e = f64::from(self.orbit.epsilon);
+        let a = f64::from(self.orbit.semi_major());
+        // Distance from the center of gravity to the center, along the direction of periapsis.
+        let distance = match self.orbit.kind() {
+            OrbitKind::Circle => return Some((0.0, 0.0)),
+            OrbitKind::Ellipse => -a * e,
+            OrbitKind::Parabola => return None,
+            OrbitKind::Hyperbola => a * e,
+        };
+        let (y, x) = math::sin_cos(f64::from(self.angle));
+        Some((x * distance, y * distance))
+    }
+
+    /// The focus that is not the center of gravity. For a hyperbola this is the focus of the other branch.
+    /// Parabolas have no second focus.
+    pub fn second_focus(&self) -> Option<(f64, f64)> {
+        let (x, y) = self.center()?;
+        Some((x * 2.0, y * 2.0))
+    }
+
+    /// Unit vectors pointing to where a hyperbola comes from and where it escapes to at infinity.
+    /// Both asymptotes pass through [Object::center].
+    pub fn asymptotes(&self) -> Option<[(f64, f64); 2]> {
+        match self.orbit.kind() {
+            OrbitKind::Hyperbola => {
+                // 1/e = -cos(angle)
+                let angle = math::acos(-1.0 / f64::from(self.orbit.epsilon));
+                let dir = |angle: f64| {
+                    let (y, x) = math::sin_cos(angle + f64::from(self.angle));
+                    (x, y)
+                };
+                Some([dir(-angle), dir(angle)])
+            }
+            OrbitKind::Circle | OrbitKind::Ellipse | OrbitKind::Parabola => None,
+        }
+    }
+}
+
+#[test]
+fn velocity_is_derivative_of_position() {
+    use crate::Orbit;
+    use core::convert::TryInto as _;
+
+    let ellipse = Orbit::from_pos_dir(
+        100.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.12.try_into().unwrap(),
+    );
+    let dt = 1e-3;
+    for &t in &[0.0, 100.0, 2000.0, 5000.0] {
+        let (x0, y0) = ellipse.position_at(t);
+        let (x1, y1) = ellipse.position_at(t + dt);
+        let (vx, vy) = ellipse.velocity_at(t + dt / 2.0);
+        assert!(
+            ((x1 - x0) / dt - vx).abs() < 1e-6,
+            "{} {}",
+            (x1 - x0) / dt,
+            vx
+        );
+        assert!(
+            ((y1 - y0) / dt - vy).abs() < 1e-6,
+            "{} {}",
+            (y1 - y0) / dt,
+            vy
+        );
+    }
+    let (px, py) = ellipse.periapsis();
+    let (ax, ay) = ellipse.apoapsis().unwrap();
+    let (cx, cy) = ellipse.center().unwrap();
+    assert!(((px + ax) / 2.0 - cx).abs() < 1e-9 && ((py + ay) / 2.0 - cy).abs() < 1e-9);
+}
@@ -16,26 +16,50 @@
 //! objects that all the nice math breaks down after a few days anyway.
 //!
 //! So yea, don't use this for anything real, but it should be precise enough for everything else.
+//!
+//! Time is the exception, as it grows forever. It is represented as a [Time], which keeps the whole
+//! time units separate from the fraction, so positions don't start jittering after a long time.
+//!
+//! ## `no_std`
+//!
+//! Disabling the default `std` feature makes this crate `no_std`, only requiring `alloc`.
+//! All float functions are computed by [libm] in both cases, so positions are the same
+//! bit-for-bit no matter which platform or feature set computed them.
 
-use std::convert::TryFrom as _;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use core::convert::TryFrom as _;
 use tracing::*;
 use typed_floats::{
     tf64::{
-        consts::{FRAC_PI_2, PI, TAU},
+        consts::{PI, TAU},
         ZERO,
     },
-    Atan2 as _, NonNaN, NonNaNFinite, NonZeroNonNaNFinite, Positive, PositiveFinite,
-    StrictlyPositiveFinite,
+    NonNaN, NonNaNFinite, NonZeroNonNaNFinite, PositiveFinite, StrictlyPositiveFinite,
 };
 
 pub use typed_floats;
+pub mod batch;
+pub mod frame;
+pub mod geometry;
+pub mod maneuver;
+mod math;
 pub mod orbits;
+pub mod scene;
+pub mod shadow;
+pub mod spatial;
+pub mod tessellation;
+pub mod time;
 
 pub use orbits::Orbits;
+pub use tessellation::Tessellation;
+pub use time::Time;
 
 use crate::orbits::Object;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Orbit {
     /// Semi-latus rectum. Basically a factor scaling the height of the ellipse.
     pub p: StrictlyPositiveFinite,
@@ -60,7 +84,7 @@ impl OrbitKind {
         assert!(e >= 0.0);
         if e < 1e-6 {
             Self::Circle
-        } else if (e - ONE).abs() < 1e-6 {
+        } else if (f64::from(e) - 1.0).abs() < 1e-6 {
             Self::Parabola
         } else if e < 1.0 {
             Self::Ellipse
@@ -70,6 +94,11 @@ impl OrbitKind {
     }
 }
 
+/// Check the result of a float function for NaN and infinity.
+fn finite(f: f64) -> NonNaNFinite {
+    NonNaNFinite::try_from(f).unwrap()
+}
+
 fn square(f: NonNaN) -> PositiveFinite {
     PositiveFinite::try_from(f * f).unwrap()
 }
@@ -91,96 +120,83 @@ impl Orbit {
     pub fn circular(radius: StrictlyPositiveFinite) -> Self {
         Self {
             p: radius,
-            epsilon: ZERO.into(),
+            epsilon: ZERO,
         }
     }
 
-    /// Compute orbit from position and speed. The second return value is the angle of the orbit.
-    /// The third return value is the starting time of the object in the orbit.
+    /// Compute the orbit of an object at position `x`/`y` moving with velocity `dx`/`dy` at time `0`.
+    ///
+    /// Objects always move counterclockwise (towards increasing angles). If the velocity points
+    /// clockwise, it is mirrored along the line to the center of gravity, which keeps the shape
+    /// and energy of the orbit, just not its direction.
     #[instrument(level = "debug")]
     pub fn from_pos_dir(x: NonNaN, y: NonNaN, dx: NonNaN, dy: NonNaN) -> Object {
         let r_squared = StrictlyPositiveFinite::try_from(square(x) + square(y)).unwrap();
-        let r = r_squared.sqrt();
-        let phi = y.atan2(x);
-        let v_squared = square(dx) + square(dy);
-        let a_over_r = ONE / NonZeroNonNaNFinite::try_from(TWO - r * v_squared).unwrap();
-        let a = NonZeroNonNaNFinite::try_from(r * a_over_r).unwrap();
-        let xi = dy.atan2(dx);
-        let sinxiphi = NonNaNFinite::try_from(xi - phi).unwrap().sin();
-        let sinxiphi2 = square(sinxiphi.into());
-        // https://phys.libretexts.org/Bookshelves/Astronomy__Cosmology/Book%3A_Celestial_Mechanics_(Tatum)/09%3A_The_Two_Body_Problem_in_Two_Dimensions/9.08%3A_Orbital_Elements_and_Velocity_Vector
-        // formula 9.9.4
-        let rvs = Positive::try_from(r_squared * v_squared * sinxiphi2).unwrap();
-        let one_neg_e_squared = rvs / a;
-        let e_squared = StrictlyPositiveFinite::try_from(ONE - one_neg_e_squared).unwrap();
-        let e = e_squared.sqrt();
-        let cos_angle = NonNaNFinite::try_from((rvs / r - ONE) / e).unwrap();
-        trace!(?cos_angle);
-        // Truncate precision to f32 to make sure we never get above 1.0 even with some float math issues
-        let angle = NonNaNFinite::<f64>::new(f64::from(cos_angle) as f32 as f64).unwrap();
-        assert!(angle.abs() <= 1.0, "{} > 1.0", angle.abs());
-        let angle = PositiveFinite::try_from(angle.acos()).unwrap();
-        let angles = [
-            NonNaNFinite::try_from(phi - angle).unwrap(),
-            NonNaNFinite::try_from(phi + angle).unwrap(),
-        ];
-        trace!(?angles);
-        let kind = OrbitKind::from_eccentricity(e.into());
-        let p = match kind {
-            OrbitKind::Circle => StrictlyPositiveFinite::try_from(a).unwrap(),
-            OrbitKind::Ellipse => StrictlyPositiveFinite::try_from(a * (ONE - e_squared)).unwrap(),
-            OrbitKind::Parabola => StrictlyPositiveFinite::try_from((cos_angle + ONE) * r).unwrap(),
-            OrbitKind::Hyperbola => {
-                StrictlyPositiveFinite::try_from(-a * (e_squared - ONE)).unwrap()
-            }
-        };
+        let r = math::sqrt(r_squared.into());
+        let phi = NonNaNFinite::try_from(math::atan2(y.into(), x.into())).unwrap();
+        let (x, y, mut dx, mut dy) = (f64::from(x), f64::from(y), f64::from(dx), f64::from(dy));
+        // Specific angular momentum. The gravitational parameter is always 1.
+        let mut h = x * dy - y * dx;
+        if h < 0.0 {
+            // Remove the tangential part of the velocity twice to mirror it.
+            let (tx, ty) = (-y / r, x / r);
+            let tangential = dx * tx + dy * ty;
+            dx -= 2.0 * tangential * tx;
+            dy -= 2.0 * tangential * ty;
+            h = -h;
+        }
+        let v_squared = dx * dx + dy * dy;
+        let r_dot_v = x * dx + y * dy;
+        // Eccentricity vector, pointing from the center of gravity to the apehelion.
+        let k = v_squared - 1.0 / r;
+        let e_x = k * x - r_dot_v * dx;
+        let e_y = k * y - r_dot_v * dy;
+        let e = PositiveFinite::try_from(math::hypot(e_x, e_y)).unwrap();
+        trace!(?e, h);
+        let kind = OrbitKind::from_eccentricity(e);
         let orbit = Orbit {
-            p,
-            epsilon: e.into(),
+            p: StrictlyPositiveFinite::try_from(h * h).unwrap(),
+            epsilon: e,
         };
-        let (t, angle) = if let OrbitKind::Circle = kind {
-            // Circle
-            (ZERO.into(), phi)
-        } else {
-            // xi is angle of direction
-            // HACK: we treat the tangent as 90° to the orbital angular position.
-            let d = [
-                NonNaNFinite::try_from(FRAC_PI_2 - xi).unwrap(),
-                NonNaNFinite::try_from(-FRAC_PI_2 - xi).unwrap(),
-            ];
-            let d = |i, j| {
-                NonNaNFinite::<f64>::abs(
-                    NonNaNFinite::try_from(angles[i] + d[j] + TAU).unwrap() % TAU,
-                )
-            };
-            let d = [d(0, 0).min(d(0, 1)), d(1, 0).min(d(1, 1))];
-            let angle = if d[0] < d[1] {
-                angle - d[0]
-            } else {
-                NonNaNFinite::try_from(angle + d[1]).unwrap()
-            };
-            // 9.8.1
-            let cos_big_e = (e + cos_angle) / (rvs / r);
-            // Truncate precision to f32 to make sure we never get above 1.0 even with some float math issues
-            let cos_big_e = NonNaNFinite::try_from((f64::from(cos_big_e) as f32) as f64).unwrap();
-            assert!(
-                cos_big_e.abs() <= 1.0,
-                "{} > 1.0 ({kind:?})",
-                cos_big_e.abs()
-            );
-            let big_e = NonNaNFinite::try_from(cos_big_e.acos()).unwrap();
-            let big_e = if angle < PI {
-                big_e
-            } else {
-                NonNaNFinite::try_from(TAU - big_e).unwrap()
-            };
-            let m = big_e - e * big_e.sin();
-            let mean_motion = orbit.mean_motion();
-            (PositiveFinite::try_from(m / mean_motion).unwrap(), angle)
+        let (angle, t) = match kind {
+            // No apehelion, so just start counting from the current position.
+            OrbitKind::Circle => (phi, 0.0),
+            _ => {
+                let angle = NonNaNFinite::try_from(math::atan2(e_y, e_x)).unwrap();
+                // Angle of the object in the orbit, in (-PI, PI].
+                let true_anomaly = f64::from(phi - angle);
+                let true_anomaly =
+                    math::rem_euclid(true_anomaly + f64::from(PI), TAU.into()) - f64::from(PI);
+                trace!(?angle, true_anomaly);
+                let e = f64::from(e);
+                let half_tan = math::tan(true_anomaly / 2.0);
+                // Time since the apehelion, negative if the object has not reached it yet.
+                let mean_anomaly = match kind {
+                    OrbitKind::Circle => unreachable!(),
+                    OrbitKind::Ellipse => {
+                        let big_e = 2.0 * math::atan(math::sqrt((1.0 - e) / (1.0 + e)) * half_tan);
+                        big_e - e * math::sin(big_e)
+                    }
+                    // Barker's equation
+                    OrbitKind::Parabola => {
+                        (half_tan + half_tan * half_tan * half_tan / 3.0)
+                            * core::f64::consts::SQRT_2
+                    }
+                    OrbitKind::Hyperbola => {
+                        let big_e = 2.0 * math::atanh(math::sqrt((e - 1.0) / (e + 1.0)) * half_tan);
+                        e * math::sinh(big_e) - big_e
+                    }
+                };
+                (angle, mean_anomaly / f64::from(orbit.mean_motion()))
+            }
+        };
+        let obj = Object {
+            angle,
+            t: t.into(),
+            orbit,
         };
-        let obj = Object { angle, t, orbit };
 
-        let actual_r = obj.r(obj.angle_at(0.0));
+        let actual_r = f64::from(obj.r(obj.angle_at(0.0)));
         if (actual_r - r).abs() > 1e-3 {
             panic!(
                 "{kind:?}: {actual_r} should be {r}, diff: {}",
@@ -194,7 +210,9 @@ impl Orbit {
     /// Radius at orbital angle `phi` in orbit coordinates, not in the coordinate system of the center of gravity.
     /// You need to adjust for the angle of the orbit yourself.
     pub fn r(&self, phi: NonNaNFinite) -> NonNaN {
-        self.p / NonZeroNonNaNFinite::try_from(ONE + self.epsilon * phi.cos()).unwrap()
+        self.p
+            / NonZeroNonNaNFinite::try_from(ONE + self.epsilon * finite(math::cos(phi.into())))
+                .unwrap()
     }
 
     /// Radius at the point closest to the center of gravity.
@@ -232,8 +250,10 @@ impl Orbit {
             OrbitKind::Circle => self.p,
             OrbitKind::Ellipse => StrictlyPositiveFinite::try_from(
                 self.p
-                    / StrictlyPositiveFinite::try_from((ONE - square(self.epsilon.into())).sqrt())
-                        .unwrap(),
+                    / StrictlyPositiveFinite::try_from(math::sqrt(
+                        (ONE - square(self.epsilon.into())).into(),
+                    ))
+                    .unwrap(),
             )
             .unwrap(),
             OrbitKind::Parabola => panic!("cannot compute semi minor axis for for parabola"),
@@ -247,7 +267,10 @@ impl Orbit {
 
     pub fn mean_motion(&self) -> StrictlyPositiveFinite {
         let semi_major = self.semi_major();
-        StrictlyPositiveFinite::try_from((ONE / semi_major).sqrt() / semi_major).unwrap()
+        StrictlyPositiveFinite::try_from(
+            math::sqrt((ONE / semi_major).into()) / f64::from(semi_major),
+        )
+        .unwrap()
     }
 
     /// This cannot be solved numerically, we loop until the precision is
@@ -255,7 +278,7 @@ impl Orbit {
     #[instrument(level = "trace")]
     pub fn eccentric_anomaly(&self, time: PositiveFinite) -> NonNaNFinite {
         let mean_motion = self.mean_motion();
-        let time_in_current_orbit = if self.epsilon < 1.0 {
+        let time_in_current_orbit = if let OrbitKind::Circle | OrbitKind::Ellipse = self.kind() {
             // Optimize repeating orbits by only computing the
             // position from the last apehelion crossing.
             time % StrictlyPositiveFinite::try_from(TAU / mean_motion).unwrap()
@@ -263,7 +286,13 @@ impl Orbit {
             time
         };
         let mean_anomaly = PositiveFinite::try_from(mean_motion * time_in_current_orbit).unwrap();
-        let mut e = NonNaNFinite::from(mean_anomaly);
+        let mut e = match self.kind() {
+            // Starting at the mean anomaly overshoots wildly for hyperbolas, as `sinh` grows so fast.
+            OrbitKind::Hyperbola => {
+                NonNaNFinite::try_from(math::asinh(mean_anomaly / self.epsilon)).unwrap()
+            }
+            _ => NonNaNFinite::from(mean_anomaly),
+        };
         let mut i = 0;
         loop {
             let old = e;
@@ -272,8 +301,8 @@ impl Orbit {
                 OrbitKind::Ellipse => {
                     // 9.6.8
                     // E = (M - e(E*cos(E) - sin(E)))/(1 - e * cos(E))
-                    let sin = e.sin();
-                    let cos = e.cos();
+                    let (sin, cos) = math::sin_cos(e.into());
+                    let (sin, cos) = (finite(sin), finite(cos));
                     e = NonNaNFinite::try_from(
                         (mean_anomaly
                             - NonNaNFinite::try_from(self.epsilon * (e * cos - sin)).unwrap())
@@ -282,29 +311,33 @@ impl Orbit {
                     .unwrap();
                 }
                 OrbitKind::Parabola => {
+                    // Barker's equation: u + u³/3 = M / sqrt(2), where u = tan(angle / 2)
+                    // Newton's method for u³ + 3u - c = 0
                     let u2 = square(e.into());
-                    let c = mean_anomaly
-                        * PI
-                        * StrictlyPositiveFinite::try_from((18.0_f64).sqrt()).unwrap();
-                    e = NonNaNFinite::try_from((TWO * u2 + c) / (THREE + THREE * u2)).unwrap();
+                    let u3 = NonNaNFinite::try_from(u2 * e).unwrap();
+                    let c =
+                        mean_anomaly * StrictlyPositiveFinite::try_from(math::sqrt(4.5)).unwrap();
+                    e = NonNaNFinite::try_from(
+                        NonNaNFinite::try_from(TWO * u3 + c).unwrap() / (THREE + THREE * u2),
+                    )
+                    .unwrap();
                 }
                 OrbitKind::Hyperbola => {
                     // 9.8.14
-                    let cosh = e.cosh();
-                    let sinh = e.sinh();
+                    let cosh = finite(math::cosh(e.into()));
+                    let sinh = finite(math::sinh(e.into()));
                     // E = (M + e(E*cosh(E) - sinh(E)))/(e * cosh(E) - 1)
                     e = NonNaNFinite::try_from(
-                        mean_anomaly
-                            + PositiveFinite::try_from(
-                                PositiveFinite::try_from(
+                        PositiveFinite::try_from(
+                            mean_anomaly
+                                + PositiveFinite::try_from(
                                     self.epsilon
                                         * (PositiveFinite::try_from(e * cosh).unwrap() - sinh),
                                 )
-                                .unwrap()
-                                    / (PositiveFinite::try_from(self.epsilon * cosh).unwrap()
-                                        - ONE),
-                            )
-                            .unwrap(),
+                                .unwrap(),
+                        )
+                        .unwrap()
+                            / (PositiveFinite::try_from(self.epsilon * cosh).unwrap() - ONE),
                     )
                     .unwrap();
                 }
@@ -319,7 +352,7 @@ impl Orbit {
                 );
                 return e;
             }
-            if delta.abs() < 1e-6 {
+            if f64::from(delta).abs() < 1e-6 {
                 return e;
             }
             i += 1;
@@ -339,28 +372,27 @@ impl Orbit {
             }
             OrbitKind::Ellipse => {
                 let e = self.eccentric_anomaly(time);
-                let x = e.cos() - self.epsilon;
-                let y = e.sin() * self.eps_squared().sqrt();
-                y.atan2(x)
+                let x = math::cos(e.into()) - f64::from(self.epsilon);
+                let y = math::sin(e.into()) * math::sqrt(self.eps_squared().into());
+                NonNaNFinite::try_from(math::atan2(y, x)).unwrap()
             }
             OrbitKind::Parabola => {
                 let e = self.eccentric_anomaly(time);
                 let u2 = e * e;
                 let cosv = NonNaNFinite::try_from((ONE - u2) / (ONE + u2)).unwrap();
-                NonNaNFinite::try_from(cosv.acos()).unwrap()
+                NonNaNFinite::try_from(math::acos(cosv.into())).unwrap()
             }
             OrbitKind::Hyperbola => {
                 let e = self.eccentric_anomaly(time);
                 // 9.8.6
                 // cos(v) = (e - cosh(E))/(e * cosh(E) - 1)
-                let cosh = e.cosh();
+                let cosh = finite(math::cosh(e.into()));
                 let cosv = NonNaNFinite::try_from(
                     (self.epsilon - cosh)
                         / (PositiveFinite::try_from(self.epsilon * cosh).unwrap() - ONE),
                 )
                 .unwrap();
-                NonNaNFinite::try_from(NonNaNFinite::try_from(cosv.acos()).unwrap() * time.signum())
-                    .unwrap()
+                NonNaNFinite::try_from(math::acos(cosv.into()) * f64::from(time).signum()).unwrap()
             }
         }
     }
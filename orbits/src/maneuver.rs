@@ -0,0 +1,224 @@
+//! Planned burns and the trajectory that results from them.
+//!
+//! A [Plan] starts from the current orbit of an object and applies each [Maneuver] in time order.
+//! Every burn ends one conic segment and starts the next, so the predicted trajectory is a chain of
+//! [Segment]s. Editing a maneuver only recomputes the segments after it.
+
+use alloc::{vec, vec::Vec};
+use core::convert::TryInto as _;
+
+use crate::{orbits::Object, time::Time, Orbit, Orbits};
+
+/// An instantaneous change of velocity at a specific time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Maneuver {
+    pub t: Time,
+    pub delta_v: (f64, f64),
+}
+
+/// One conic of a predicted trajectory.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    pub object: Object,
+    /// The maneuver that started this segment, `None` for the orbit before any maneuvers.
+    pub start: Option<Time>,
+    /// The maneuver that ends this segment, `None` for the orbit after all maneuvers.
+    pub end: Option<Time>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Plan {
+    /// Sorted by time.
+    maneuvers: Vec<Maneuver>,
+    /// One more than `maneuvers`, unless a burn sends the object straight into the center of gravity,
+    /// in which case nothing after that burn is predicted.
+    segments: Vec<Segment>,
+}
+
+impl Plan {
+    pub fn new(object: Object) -> Self {
+        Self {
+            maneuvers: Vec::new(),
+            segments: vec![Segment {
+                object,
+                start: None,
+                end: None,
+            }],
+        }
+    }
+
+    pub fn maneuvers(&self) -> &[Maneuver] {
+        &self.maneuvers
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Add a maneuver, returning its index among all maneuvers of this plan.
+    pub fn insert(&mut self, maneuver: Maneuver) -> usize {
+        let idx = self.maneuvers.partition_point(|m| m.t <= maneuver.t);
+        self.maneuvers.insert(idx, maneuver);
+        self.recompute(idx);
+        idx
+    }
+
+    /// Replace the maneuver at `idx`, returning its new index, as changing its time may reorder it.
+    pub fn edit(&mut self, idx: usize, maneuver: Maneuver) -> usize {
+        self.maneuvers.remove(idx);
+        let new = self.maneuvers.partition_point(|m| m.t <= maneuver.t);
+        self.maneuvers.insert(new, maneuver);
+        // Segments are only valid up to the first maneuver that changed.
+        self.recompute(idx.min(new));
+        new
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Maneuver {
+        let maneuver = self.maneuvers.remove(idx);
+        self.recompute(idx);
+        maneuver
+    }
+
+    /// Recompute all segments after the maneuver at `idx`.
+    fn recompute(&mut self, idx: usize) {
+        // There may be fewer segments if an earlier burn ended the trajectory.
+        let idx = idx.min(self.segments.len() - 1);
+        self.segments.truncate(idx + 1);
+        let mut segment = self.segments[idx];
+        for maneuver in &self.maneuvers[idx..] {
+            let object = &segment.object;
+            let (x, y) = object.position_at(maneuver.t);
+            let (dx, dy) = object.velocity_at(maneuver.t);
+            let (dx, dy) = (dx + maneuver.delta_v.0, dy + maneuver.delta_v.1);
+            if (x * dy - y * dx).abs() < 1e-12 {
+                break;
+            }
+            // Objects are at the given position at time 0, so shift it to the time of the maneuver.
+            let mut object = Orbit::from_pos_dir(
+                x.try_into().unwrap(),
+                y.try_into().unwrap(),
+                dx.try_into().unwrap(),
+                dy.try_into().unwrap(),
+            );
+            object.t += -maneuver.t;
+            self.segments.last_mut().unwrap().end = Some(maneuver.t);
+            segment = Segment {
+                object,
+                start: Some(maneuver.t),
+                end: None,
+            };
+            self.segments.push(segment);
+        }
+        // Either no maneuvers follow, or the one that sent the object into the center of gravity.
+        let end = self.maneuvers.get(self.segments.len() - 1).map(|m| m.t);
+        self.segments.last_mut().unwrap().end = end;
+    }
+
+    /// The segment the object is on at time `t`.
+    pub fn segment_at(&self, t: impl Into<Time>) -> &Segment {
+        let t = t.into();
+        let idx = self
+            .segments
+            .partition_point(|segment| segment.start.is_none_or(|start| start <= t));
+        &self.segments[idx.saturating_sub(1)]
+    }
+
+    /// Predicted position at time `t`, taking all maneuvers before `t` into account.
+    pub fn position_at(&self, t: impl Into<Time>) -> (f64, f64) {
+        let t = t.into();
+        self.segment_at(t).object.position_at(t)
+    }
+
+    /// Tessellate every segment of the trajectory from time `now` on, in order.
+    /// The last segment is drawn for one orbit, or until it leaves `max_radius`.
+    /// See [Object::arc] for details.
+    pub fn arcs(
+        &self,
+        now: impl Into<Time>,
+        max_error: f64,
+        max_radius: f64,
+    ) -> impl Iterator<Item = Vec<(f64, f64)>> + '_ {
+        let now = now.into();
+        self.segments
+            .iter()
+            .filter(move |segment| segment.end.is_none_or(|end| end > now))
+            .map(move |segment| {
+                let from = segment
+                    .start
+                    .map_or(now, |start| if start > now { start } else { now });
+                let to = segment
+                    .end
+                    .unwrap_or_else(|| from + segment.object.period().unwrap_or(1e12));
+                segment.object.arc(from, to, max_error, max_radius)
+            })
+    }
+}
+
+impl Orbits {
+    /// The planned maneuvers of the object `id`, if any were planned.
+    pub fn plan(&self, id: usize) -> Option<&Plan> {
+        self.plans.get(&id)
+    }
+
+    /// The planned maneuvers of the object `id`, starting a new plan from its current orbit if there is none.
+    pub fn plan_mut(&mut self, id: usize) -> Option<&mut Plan> {
+        let object = *self.get(id)?;
+        Some(self.plans.entry(id).or_insert_with(|| Plan::new(object)))
+    }
+
+    /// Forget all planned maneuvers of the object `id`.
+    pub fn clear_plan(&mut self, id: usize) -> Option<Plan> {
+        self.plans.remove(&id)
+    }
+}
+
+#[test]
+fn burns_chain_segments() {
+    let circle = Object {
+        angle: 0.0.try_into().unwrap(),
+        t: Time::ZERO,
+        orbit: Orbit::circular(100.0.try_into().unwrap()),
+    };
+    let speed: f64 = 0.1;
+    let mut plan = Plan::new(circle);
+    assert_eq!(
+        plan.insert(Maneuver {
+            t: 1000.0.into(),
+            delta_v: (0.0, 0.0),
+        }),
+        0
+    );
+    let quarter = circle.period().unwrap() / 4.0;
+    // Prograde burn at the start raises the opposite side of the orbit.
+    let burn = plan.insert(Maneuver {
+        t: Time::ZERO,
+        delta_v: (0.0, 0.02),
+    });
+    assert_eq!(burn, 0);
+    assert_eq!(plan.segments().len(), 3);
+    let ellipse = plan.segments()[1].object;
+    assert!((f64::from(ellipse.orbit.p) - (100.0 * (speed + 0.02)).powi(2)).abs() < 1e-9);
+    assert_eq!(plan.segments()[1].end, Some(1000.0.into()));
+    assert!(plan.segments()[2].end.is_none());
+
+    // Moving the burn later recomputes everything after it.
+    let burn = plan.edit(
+        burn,
+        Maneuver {
+            t: Time::from(quarter),
+            delta_v: (-0.02, 0.0),
+        },
+    );
+    assert_eq!(burn, 1);
+    assert_eq!(plan.segments()[0].end, Some(1000.0.into()));
+    assert_eq!(plan.segments()[2].start, Some(Time::from(quarter)));
+    let (x, y) = plan.position_at(quarter);
+    let expected = circle.position_at(quarter);
+    assert!((x - expected.0).abs() < 1e-6 && (y - expected.1).abs() < 1e-6);
+    assert_eq!(plan.arcs(0.0, 0.5, 1000.0).count(), 3);
+    assert_eq!(plan.arcs(quarter + 1.0, 0.5, 1000.0).count(), 1);
+
+    plan.remove(0);
+    assert_eq!(plan.segments().len(), 2);
+    assert_eq!(plan.segments()[0].end, Some(Time::from(quarter)));
+}
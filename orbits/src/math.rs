@@ -0,0 +1,23 @@
+//! Float functions that don't depend on `std`.
+//!
+//! `std` forwards most of these to the C library of the platform, whose results can differ in
+//! the last bits, so everything goes through [libm] instead, even when `std` is available.
+
+pub(crate) use libm::{
+    acos, asinh, atan, atan2, atanh, cos, cosh, floor, hypot, sin, sinh, sqrt, tan,
+};
+
+/// Same as [f64::rem_euclid].
+pub(crate) fn rem_euclid(lhs: f64, rhs: f64) -> f64 {
+    let r = lhs % rhs;
+    if r < 0.0 {
+        r + rhs.abs()
+    } else {
+        r
+    }
+}
+
+/// Same as [f64::sin_cos].
+pub(crate) fn sin_cos(x: f64) -> (f64, f64) {
+    (sin(x), cos(x))
+}
@@ -1,51 +1,132 @@
-use std::{collections::HashMap, convert::TryFrom as _, f64::consts::TAU};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{convert::TryFrom as _, f64::consts::TAU};
 
 use typed_floats::{NonNaN, NonNaNFinite, PositiveFinite};
 
-use crate::{Orbit, OrbitKind};
+use crate::{
+    batch::Batch, maneuver::Plan, tessellation::Tessellation, time::Time, Orbit, OrbitKind,
+};
 
+#[derive(Clone, Copy, Debug)]
 pub struct Object {
     /// Angle of apehelion.
     pub angle: NonNaNFinite,
-    /// Starting point of object in the orbit.
-    pub t: PositiveFinite,
+    /// Starting point of object in the orbit. The object is at its apehelion
+    /// whenever the current time plus `t` is a multiple of the period.
+    pub t: Time,
     /// raw orbit information.
     pub orbit: Orbit,
 }
 
 impl Object {
-    pub fn angle_at(&self, t: f64) -> NonNaNFinite {
-        self.orbit.angle_at(
-            PositiveFinite::try_from(PositiveFinite::try_from(t).unwrap() + self.t).unwrap(),
-        )
+    pub fn angle_at(&self, t: impl Into<Time>) -> NonNaNFinite {
+        let time = t.into() + self.t;
+        match self.period() {
+            Some(period) => self
+                .orbit
+                .angle_at(PositiveFinite::try_from(time.rem_euclid(period)).unwrap()),
+            None => {
+                // Open orbits are symmetric around their apehelion, so objects that
+                // haven't reached it yet mirror the ones that have passed it.
+                let time = time.as_f64();
+                let angle = self
+                    .orbit
+                    .angle_at(PositiveFinite::try_from(time.abs()).unwrap());
+                if time < 0.0 {
+                    -angle
+                } else {
+                    angle
+                }
+            }
+        }
     }
 
     pub fn r(&self, angle: NonNaNFinite) -> NonNaN {
         self.orbit.r(angle)
     }
+
+    /// Time it takes to go around the orbit once. Open orbits never come back.
+    pub fn period(&self) -> Option<f64> {
+        match self.orbit.kind() {
+            OrbitKind::Circle | OrbitKind::Ellipse => {
+                Some(TAU / f64::from(self.orbit.mean_motion()))
+            }
+            OrbitKind::Parabola | OrbitKind::Hyperbola => None,
+        }
+    }
+
+    /// Move the starting point to within the first period of the orbit, without
+    /// changing where the object is at any time. Returns whether anything changed.
+    /// This keeps the starting point small after maneuvers were computed at large times.
+    pub fn rebase(&mut self) -> bool {
+        match self.period() {
+            Some(period) if !(0.0..period).contains(&self.t.as_f64()) => {
+                self.t = Time::from(self.t.rem_euclid(period));
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Orbits {
-    sparse: HashMap<usize, usize>,
+    sparse: BTreeMap<usize, usize>,
     next_id: usize,
     objects: Vec<Object>,
+    /// Derived quantities of all `objects`, in the same order.
+    batch: Batch,
+    /// Planned maneuvers of some of the objects, by id.
+    pub(crate) plans: BTreeMap<usize, Plan>,
 }
 
 impl Orbits {
-    /// Insert a new object. This operation is `O(1)`
+    /// Insert a new object. This operation is `O(log N)`
     pub fn insert(&mut self, object: Object) -> usize {
         let id = self.next_id;
         self.next_id += 1;
         self.sparse.insert(id, self.objects.len());
+        self.batch.push(&object);
         self.objects.push(object);
         id
     }
 
+    /// Insert an object with a specific id, e.g. when restoring saved objects. Returns `false` and
+    /// does nothing if the id is taken already. [insert](Self::insert) never hands out this id afterwards.
+    pub fn insert_with_id(&mut self, id: usize, object: Object) -> bool {
+        if self.sparse.contains_key(&id) {
+            return false;
+        }
+        self.next_id = self.next_id.max(id + 1);
+        self.sparse.insert(id, self.objects.len());
+        self.batch.push(&object);
+        self.objects.push(object);
+        true
+    }
+
+    /// All objects and their ids, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Object)> + '_ {
+        self.sparse
+            .iter()
+            .map(move |(&id, &idx)| (id, &self.objects[idx]))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Object> {
+        self.objects.get(*self.sparse.get(&id)?)
+    }
+
+    /// Index of the object with the given `id` in the [batch](Self::batch).
+    /// Removing objects shifts the indices of the objects after them.
+    pub fn index(&self, id: usize) -> Option<usize> {
+        self.sparse.get(&id).copied()
+    }
+
     /// Remove an object. If it wasn't the last object to be removed,
     /// this operation may be expensive (`O(N)`).
     pub fn remove(&mut self, id: usize) -> Option<Object> {
         let idx = self.sparse.remove(&id)?;
+        self.plans.remove(&id);
+        self.batch.remove(idx);
         if self.objects.len() - 1 == idx {
             self.objects.pop()
         } else {
@@ -58,60 +139,57 @@ impl Orbits {
         }
     }
 
+    /// [Rebase](Object::rebase) all objects.
+    pub fn rebase(&mut self) {
+        for (idx, object) in self.objects.iter_mut().enumerate() {
+            if object.rebase() {
+                self.batch.set_t(idx, object.t);
+            }
+        }
+    }
+
+    /// The cached orbital elements of all objects, for propagating them in bulk.
+    pub fn batch(&self) -> &Batch {
+        &self.batch
+    }
+
+    /// Compute the position of all objects at time `t`, without their orbits.
+    /// See [Batch::propagate] for details.
+    pub fn positions(&self, t: impl Into<Time>, positions: &mut Vec<(f32, f32)>) {
+        self.batch.propagate(t, positions)
+    }
+
     /// Compute the position of all objects at time `t` and their corresponding orbits.
-    /// The segments iterator is zero cost if unused.
+    /// The points iterator is zero cost if unused.
     pub fn draw(
         &self,
-        t: f64,
-        segments: i32,
+        t: impl Into<Time>,
+        tessellation: Tessellation,
     ) -> impl Iterator<Item = (OrbitKind, (f32, f32), impl Iterator<Item = (f32, f32)> + '_)> + '_
     {
-        self.objects.iter().map(move |object| {
-            let angle = object.angle_at(t);
-            let radius = object.r(angle);
-            let system_angle = f64::from(angle + object.angle);
-            let y = system_angle.sin();
-            let x = system_angle.cos();
-            let x = x as f32;
-            let y = y as f32;
-            let pos_x = x * f64::from(radius) as f32;
-            let pos_y = y * f64::from(radius) as f32;
-
-            let kind = object.orbit.kind();
-            let mut step_size_start = None;
-            (
-                kind,
-                (pos_x, pos_y),
-                (0..segments).map(move |i| {
-                    // FIXME: try out starting at the object position in case that is cheaper
-                    let (step_size, start) = *step_size_start.get_or_insert_with(|| {
-                        let (start, range) = match kind {
-                            OrbitKind::Circle | OrbitKind::Ellipse => (0.0, TAU),
-                            OrbitKind::Parabola | OrbitKind::Hyperbola => {
-                                // 1/e = cos(angle)
-                                let angle = (-1.0 / f64::from(object.orbit.epsilon)).acos();
-                                let range = angle * 2.0;
-                                // Subtract one degree so we don't render over infinity.
-                                (-angle + TAU / 360.0, range - TAU / 180.0)
-                            }
-                        };
-                        let step_size = range / segments as f64;
-                        (step_size, start)
-                    });
-                    let angle = step_size * (i + 1) as f64 + start;
-                    let (new_y, new_x) = angle.sin_cos();
-                    let mut new_x = new_x as f32;
-                    let mut new_y = new_y as f32;
-                    let r = f64::from(
-                        object
-                            .orbit
-                            .r(NonNaNFinite::try_from(angle - f64::from(object.angle)).unwrap()),
-                    ) as f32;
-                    new_y *= r;
-                    new_x *= r;
-                    (new_x, new_y)
-                }),
-            )
+        let t = t.into();
+        self.objects.iter().enumerate().map(move |(idx, object)| {
+            let (pos_x, pos_y) = self.batch.position(idx, t);
+            let (pos_x, pos_y) = (pos_x as f32, pos_y as f32);
+            let points = core::iter::once_with(move || {
+                object.tessellate(self.batch.angle_at(idx, t), tessellation)
+            })
+            .flatten()
+            .map(|(x, y)| (x as f32, y as f32));
+            (object.orbit.kind(), (pos_x, pos_y), points)
         })
     }
+
+    /// Tessellate the path the object with the given `id` takes between the times `from` and `to`.
+    /// See [Object::arc] for details.
+    pub fn arc(
+        &self,
+        id: usize,
+        from: impl Into<Time>,
+        to: impl Into<Time>,
+        max_error: f64,
+        max_radius: f64,
+    ) -> Option<Vec<(f64, f64)>> {
+        Some(self.get(id)?.arc(from, to, max_error, max_radius))
+    }
 }
@@ -0,0 +1,175 @@
+//! A plain text format for objects, so sets of orbits can be authored by hand or in the
+//! `solar_playground` example and loaded by the game.
+//!
+//! Each object is one line of `key=value` pairs, e.g. `angle=0 t=0+0 p=200 epsilon=1`.
+//! Empty lines and lines starting with `#` are ignored when parsing [Orbits].
+
+use alloc::{borrow::ToOwned as _, string::String};
+use core::{
+    convert::TryFrom as _,
+    fmt::{self, Display},
+    num::ParseFloatError,
+    str::FromStr,
+};
+
+use typed_floats::{InvalidNumber, NonNaNFinite, PositiveFinite, StrictlyPositiveFinite};
+
+use crate::{orbits::Object, Orbit, Orbits};
+
+#[derive(Debug)]
+pub enum ParseObjectError {
+    /// A required key was not given.
+    Missing(&'static str),
+    /// A key that is not part of the format, or a pair without a `=`.
+    Unknown(String),
+    Float(&'static str, ParseFloatError),
+    /// The value parsed fine, but is out of range, e.g. a negative eccentricity.
+    Invalid(&'static str, InvalidNumber),
+}
+
+impl Display for ParseObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(key) => write!(f, "missing `{key}`"),
+            Self::Unknown(pair) => write!(f, "unknown `{pair}`"),
+            Self::Float(key, err) => write!(f, "`{key}`: {err}"),
+            Self::Invalid(key, err) => write!(f, "`{key}`: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ParseObjectError {}
+
+#[derive(Debug)]
+pub struct ParseSceneError {
+    /// 1-based line number of the offending object.
+    pub line: usize,
+    pub error: ParseObjectError,
+}
+
+impl Display for ParseSceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl core::error::Error for ParseSceneError {}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "angle={} t={} p={} epsilon={}",
+            f64::from(self.angle),
+            self.t,
+            f64::from(self.orbit.p),
+            f64::from(self.orbit.epsilon),
+        )
+    }
+}
+
+impl FromStr for Object {
+    type Err = ParseObjectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut angle, mut t, mut p, mut epsilon) = (None, None, None, None);
+        for pair in s.split_whitespace() {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| ParseObjectError::Unknown(pair.to_owned()))?;
+            let float = |key| {
+                value
+                    .parse::<f64>()
+                    .map_err(|err| ParseObjectError::Float(key, err))
+            };
+            match key {
+                "angle" => angle = Some(float("angle")?),
+                "t" => {
+                    t = Some(
+                        value
+                            .parse()
+                            .map_err(|err| ParseObjectError::Float("t", err))?,
+                    )
+                }
+                "p" => p = Some(float("p")?),
+                "epsilon" => epsilon = Some(float("epsilon")?),
+                _ => return Err(ParseObjectError::Unknown(pair.to_owned())),
+            }
+        }
+        let field = |value: Option<f64>, key| value.ok_or(ParseObjectError::Missing(key));
+        let invalid = |key| move |err| ParseObjectError::Invalid(key, err);
+        Ok(Object {
+            angle: NonNaNFinite::try_from(field(angle, "angle")?).map_err(invalid("angle"))?,
+            t: t.ok_or(ParseObjectError::Missing("t"))?,
+            orbit: Orbit {
+                p: StrictlyPositiveFinite::try_from(field(p, "p")?).map_err(invalid("p"))?,
+                epsilon: PositiveFinite::try_from(field(epsilon, "epsilon")?)
+                    .map_err(invalid("epsilon"))?,
+            },
+        })
+    }
+}
+
+/// One object per line, in insertion order.
+impl Display for Orbits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (_, object) in self.iter() {
+            writeln!(f, "{object}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Orbits {
+    type Err = ParseSceneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut orbits = Orbits::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let object = line
+                .parse()
+                .map_err(|error| ParseSceneError { line: i + 1, error })?;
+            orbits.insert(object);
+        }
+        Ok(orbits)
+    }
+}
+
+#[test]
+fn roundtrip() {
+    use crate::time::Time;
+    use core::convert::TryInto as _;
+
+    let mut orbits = Orbits::default();
+    let mut ellipse = Orbit::from_pos_dir(
+        (-120.0).try_into().unwrap(),
+        35.5.try_into().unwrap(),
+        0.01.try_into().unwrap(),
+        (-0.07).try_into().unwrap(),
+    );
+    ellipse.t += Time::new(1_000_000_000_000, 0.1);
+    orbits.insert(ellipse);
+    orbits.insert("angle=0 t=0 p=200 epsilon=1".parse().unwrap());
+    let text = orbits.to_string();
+    let parsed: Orbits = format!("# comment\n\n{text}").parse().unwrap();
+    assert_eq!(parsed.to_string(), text);
+    assert_eq!(
+        parsed.get(0).unwrap().position_at(12.5),
+        ellipse.position_at(12.5)
+    );
+
+    let err = "angle=0 t=0 p=200\nangle=0 t=0 p=-1 epsilon=0"
+        .parse::<Orbits>()
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "line 1: missing `epsilon`");
+    let err = "angle=0 t=0 p=-1 epsilon=0"
+        .parse::<Object>()
+        .err()
+        .unwrap();
+    assert!(matches!(err, ParseObjectError::Invalid("p", _)));
+}
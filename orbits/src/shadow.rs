@@ -0,0 +1,193 @@
+//! Shadows cast by the center of gravity.
+//!
+//! The light source is so far away that it is described only by the direction it is in and
+//! how large it appears. The body at the center of gravity then casts a cone of full shadow
+//! (the umbra) that narrows with distance, surrounded by a cone of partial shadow (the penumbra)
+//! that widens with distance.
+
+use alloc::vec::Vec;
+
+use crate::{math, orbits::Object, time::Time};
+
+/// A light source infinitely far away.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    /// Unit vector pointing towards the light source.
+    direction: (f64, f64),
+    /// Half the angle the light source covers in the sky.
+    angular_radius: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shadow {
+    Sunlit,
+    /// Part of the light source is hidden behind the body.
+    Penumbra,
+    /// All of the light source is hidden behind the body.
+    Umbra,
+}
+
+impl Light {
+    /// `direction` points towards the light source and does not need to be normalized.
+    pub fn new((x, y): (f64, f64), angular_radius: f64) -> Self {
+        let len = math::hypot(x, y);
+        assert!(len > 0.0, "light needs a direction");
+        assert!(
+            (0.0..core::f64::consts::FRAC_PI_2).contains(&angular_radius),
+            "angular radius must be in [0, PI/2), not {}",
+            angular_radius
+        );
+        Self {
+            direction: (x / len, y / len),
+            angular_radius,
+        }
+    }
+
+    pub fn direction(&self) -> (f64, f64) {
+        self.direction
+    }
+
+    /// Radius of the umbra at `distance` behind the center of a body with radius `body_radius`.
+    /// Negative behind the tip of the umbra cone.
+    pub fn umbra_radius(&self, body_radius: f64, distance: f64) -> f64 {
+        body_radius / math::cos(self.angular_radius) - distance * math::tan(self.angular_radius)
+    }
+
+    /// Radius of the penumbra at `distance` behind the center of a body with radius `body_radius`.
+    pub fn penumbra_radius(&self, body_radius: f64, distance: f64) -> f64 {
+        body_radius / math::cos(self.angular_radius) + distance * math::tan(self.angular_radius)
+    }
+
+    /// Distance behind the body and distance from the shadow axis of `point`.
+    fn shadow_coordinates(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (dx, dy) = self.direction;
+        (-(x * dx + y * dy), (x * dy - y * dx).abs())
+    }
+
+    /// Whether `point` is in the shadow of a body with radius `body_radius` at the center of gravity.
+    pub fn shadow(&self, body_radius: f64, point: (f64, f64)) -> Shadow {
+        let (distance, offset) = self.shadow_coordinates(point);
+        if distance <= 0.0 || offset >= self.penumbra_radius(body_radius, distance) {
+            Shadow::Sunlit
+        } else if offset <= self.umbra_radius(body_radius, distance) {
+            Shadow::Umbra
+        } else {
+            Shadow::Penumbra
+        }
+    }
+
+    /// Fraction of the light reaching `point`, from `0.0` in the umbra to `1.0` in full light.
+    /// Within the penumbra this grows linearly from the umbra to the edge of the penumbra,
+    /// which is not exact, but close enough to fade things in and out.
+    pub fn illumination(&self, body_radius: f64, point: (f64, f64)) -> f64 {
+        match self.shadow(body_radius, point) {
+            Shadow::Sunlit => 1.0,
+            Shadow::Umbra => 0.0,
+            Shadow::Penumbra => {
+                let (distance, offset) = self.shadow_coordinates(point);
+                let umbra = self.umbra_radius(body_radius, distance);
+                let penumbra = self.penumbra_radius(body_radius, distance);
+                ((offset - umbra) / (penumbra - umbra)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+impl Object {
+    /// Whether the object is in the shadow of a body with radius `body_radius` at time `t`.
+    pub fn shadow_at(&self, t: impl Into<Time>, body_radius: f64, light: &Light) -> Shadow {
+        light.shadow(body_radius, self.position_at(t))
+    }
+
+    /// All times between `from` and `to` at which the object enters or leaves a shadow,
+    /// together with the kind of shadow it is in from then on.
+    ///
+    /// The orbit is scanned in steps small enough to never jump over the shadow of the body,
+    /// so the cost grows with the length of the time span divided by the size of the body.
+    /// A body without size casts no shadow, so there are no changes.
+    pub fn shadow_changes(
+        &self,
+        from: impl Into<Time>,
+        to: impl Into<Time>,
+        body_radius: f64,
+        light: &Light,
+    ) -> Vec<(Time, Shadow)> {
+        let (from, to) = (from.into(), to.into());
+        // Fastest speed of the object, reached at the periapsis.
+        let max_speed = (1.0 + f64::from(self.orbit.epsilon)) / math::sqrt(f64::from(self.orbit.p));
+        let step = body_radius / 4.0 / max_speed;
+        let mut changes = Vec::new();
+        // A step of zero or NaN would never move `t` forward.
+        if step.is_nan() || step <= 0.0 {
+            return changes;
+        }
+        let mut t = from;
+        let mut state = self.shadow_at(t, body_radius, light);
+        while t < to {
+            let next = if to - t > step { t + step } else { to };
+            if self.shadow_at(next, body_radius, light) == state {
+                t = next;
+                continue;
+            }
+            // Bisect down to the moment the state changes.
+            let (mut lo, mut hi) = (t, next);
+            for _ in 0..40 {
+                let mid = lo + (hi - lo) / 2.0;
+                if self.shadow_at(mid, body_radius, light) == state {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            state = self.shadow_at(hi, body_radius, light);
+            changes.push((hi, state));
+            t = hi;
+        }
+        changes
+    }
+}
+
+#[test]
+fn circular_orbit_passes_through_shadow() {
+    use crate::Orbit;
+    use core::{convert::TryInto as _, f64::consts::PI};
+
+    let object = Object {
+        angle: 0.0.try_into().unwrap(),
+        t: Time::ZERO,
+        orbit: Orbit::circular(200.0.try_into().unwrap()),
+    };
+    let period = object.period().unwrap();
+    let at_angle = |angle: f64| angle / (2.0 * PI) * period;
+
+    // Parallel light casts a shadow exactly as wide as the body.
+    let light = Light::new((0.0, 1.0), 0.0);
+    let changes = object.shadow_changes(0.0, period, 20.0, &light);
+    let states: Vec<_> = changes.iter().map(|&(_, state)| state).collect();
+    assert_eq!(states, [Shadow::Umbra, Shadow::Sunlit]);
+    let enter = PI + 0.1_f64.acos();
+    let exit = 2.0 * PI - 0.1_f64.acos();
+    assert!((changes[0].0.as_f64() - at_angle(enter)).abs() < 1e-6);
+    assert!((changes[1].0.as_f64() - at_angle(exit)).abs() < 1e-6);
+
+    // A large light source adds a penumbra around the umbra.
+    let light = Light::new((0.0, 1.0), 0.05);
+    let changes = object.shadow_changes(0.0, period, 20.0, &light);
+    let states: Vec<_> = changes.iter().map(|&(_, state)| state).collect();
+    assert_eq!(
+        states,
+        [
+            Shadow::Penumbra,
+            Shadow::Umbra,
+            Shadow::Penumbra,
+            Shadow::Sunlit
+        ]
+    );
+    let bottom = object.position_at(at_angle(1.5 * PI));
+    assert_eq!(light.illumination(20.0, bottom), 0.0);
+    assert_eq!(light.illumination(20.0, (0.0, 200.0)), 1.0);
+
+    for body_radius in [0.0, -1.0, f64::NAN] {
+        assert_eq!(object.shadow_changes(0.0, period, body_radius, &light), []);
+    }
+}
@@ -0,0 +1,211 @@
+//! Proximity queries over orbiting objects.
+//!
+//! Positions change every frame, so a [SpatialIndex] is a snapshot of all objects at one point in time.
+//! Building it is a single pass over all objects (see [Orbits::spatial_index]), after which each query
+//! only looks at the grid cells that overlap the queried area instead of scanning every object.
+//! Build one per frame and use it for all the selection, collision and sensor checks of that frame.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{math, time::Time, Orbits};
+
+/// Ids and positions of the objects in one grid cell.
+type Cell = Vec<(usize, (f64, f64))>;
+
+/// Uniform grid over the positions of all objects at one point in time.
+pub struct SpatialIndex {
+    cell_size: f64,
+    cells: BTreeMap<(i64, i64), Cell>,
+    /// Smallest and largest occupied cell coordinates, to know when to stop searching outwards.
+    bounds: Option<((i64, i64), (i64, i64))>,
+}
+
+impl Orbits {
+    /// Snapshot the positions of all objects at time `t`.
+    /// `cell_size` should be around the typical query radius.
+    pub fn spatial_index(&self, t: impl Into<Time>, cell_size: f64) -> SpatialIndex {
+        let t = t.into();
+        let mut index = SpatialIndex::new(cell_size);
+        for (id, object) in self.iter() {
+            index.insert(id, object.position_at(t));
+        }
+        index
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f64) -> Self {
+        assert!(
+            cell_size > 0.0,
+            "cell size must be positive, not {}",
+            cell_size
+        );
+        Self {
+            cell_size,
+            cells: BTreeMap::new(),
+            bounds: None,
+        }
+    }
+
+    fn cell(&self, (x, y): (f64, f64)) -> (i64, i64) {
+        (
+            math::floor(x / self.cell_size) as i64,
+            math::floor(y / self.cell_size) as i64,
+        )
+    }
+
+    /// Add the object `id` at position `pos`.
+    pub fn insert(&mut self, id: usize, pos: (f64, f64)) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((id, pos));
+        let (min, max) = self.bounds.get_or_insert((cell, cell));
+        min.0 = min.0.min(cell.0);
+        min.1 = min.1.min(cell.1);
+        max.0 = max.0.max(cell.0);
+        max.1 = max.1.max(cell.1);
+    }
+
+    /// All objects in the cells overlapping the rectangle from `min` to `max`.
+    fn candidates(
+        &self,
+        min: (f64, f64),
+        max: (f64, f64),
+    ) -> impl Iterator<Item = &(usize, (f64, f64))> + '_ {
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        // Don't walk over empty cells outside of the area where there are any objects.
+        let ((bound_min_x, bound_min_y), (bound_max_x, bound_max_y)) =
+            self.bounds.unwrap_or(((0, 0), (-1, -1)));
+        let xs = min_x.max(bound_min_x)..=max_x.min(bound_max_x);
+        let ys = min_y.max(bound_min_y)..=max_y.min(bound_max_y);
+        xs.flat_map(move |x| ys.clone().map(move |y| (x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    /// Ids of all objects inside the rectangle from `min` to `max`.
+    pub fn in_rect(&self, min: (f64, f64), max: (f64, f64)) -> Vec<usize> {
+        self.candidates(min, max)
+            .filter(|(_, (x, y))| (min.0..=max.0).contains(x) && (min.1..=max.1).contains(y))
+            .map(|&(id, _)| id)
+            .collect()
+    }
+
+    /// Ids of all objects at most `radius` away from `point`.
+    pub fn within_radius(&self, point: (f64, f64), radius: f64) -> Vec<usize> {
+        let min = (point.0 - radius, point.1 - radius);
+        let max = (point.0 + radius, point.1 + radius);
+        self.candidates(min, max)
+            .filter(|(_, pos)| distance_squared(*pos, point) <= radius * radius)
+            .map(|&(id, _)| id)
+            .collect()
+    }
+
+    /// The object closest to `point` and its distance, or `None` if there are no objects.
+    pub fn nearest(&self, point: (f64, f64)) -> Option<(usize, f64)> {
+        let bounds = self.bounds?;
+        let ((min_x, min_y), (max_x, max_y)) = bounds;
+        let (x, y) = self.cell(point);
+        // Rings closer than the occupied cells are empty, rings further out than all of them are not needed.
+        let min_ring = (min_x - x)
+            .max(x - max_x)
+            .max(min_y - y)
+            .max(y - max_y)
+            .max(0);
+        let max_ring = (x - min_x)
+            .abs()
+            .max((x - max_x).abs())
+            .max((y - min_y).abs())
+            .max((y - max_y).abs());
+        let mut best: Option<(usize, f64)> = None;
+        for ring in min_ring..=max_ring {
+            // Everything in this ring or further out is at least this far away.
+            let ring_distance = (ring - 1).max(0) as f64 * self.cell_size;
+            if matches!(best, Some((_, distance)) if distance < ring_distance) {
+                break;
+            }
+            for cell in ring_cells((x, y), ring, bounds) {
+                for &(id, pos) in self.cells.get(&cell).into_iter().flatten() {
+                    let distance = math::sqrt(distance_squared(pos, point));
+                    if best.is_none_or(|(_, best)| distance < best) {
+                        best = Some((id, distance));
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+/// The cells exactly `ring` cells away from `center` in x or y direction, clipped to `bounds`.
+fn ring_cells(
+    (x, y): (i64, i64),
+    ring: i64,
+    ((min_x, min_y), (max_x, max_y)): ((i64, i64), (i64, i64)),
+) -> impl Iterator<Item = (i64, i64)> {
+    let xs = (x - ring).max(min_x)..=(x + ring).min(max_x);
+    // The corners are part of the rows.
+    let ys = (y - ring + 1).max(min_y)..=(y + ring - 1).min(max_y);
+    // The innermost ring is a single cell, so its top row is its bottom row.
+    let sides = if ring == 0 { 1 } else { 2 };
+    let rows = IntoIterator::into_iter([y - ring, y + ring])
+        .take(sides)
+        .filter(move |row| (min_y..=max_y).contains(row))
+        .flat_map(move |row| xs.clone().map(move |cx| (cx, row)));
+    let columns = IntoIterator::into_iter([x - ring, x + ring])
+        .take(sides)
+        .filter(move |column| (min_x..=max_x).contains(column))
+        .flat_map(move |column| ys.clone().map(move |cy| (column, cy)));
+    rows.chain(columns)
+}
+
+fn distance_squared(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+    dx * dx + dy * dy
+}
+
+#[test]
+fn queries_match_linear_scan() {
+    let mut index = SpatialIndex::new(10.0);
+    let mut positions = Vec::new();
+    // Deterministic scattering of points, including negative coordinates and cell borders.
+    for id in 0..200 {
+        let pos = (
+            ((id * 37) % 101) as f64 * 1.7 - 80.0,
+            ((id * 53) % 89) as f64 * 2.3 - 100.0,
+        );
+        index.insert(id, pos);
+        positions.push(pos);
+    }
+    let point = (3.0, -7.5);
+    let mut within = index.within_radius(point, 25.0);
+    within.sort_unstable();
+    let expected: Vec<_> = (0..positions.len())
+        .filter(|&id| distance_squared(positions[id], point) <= 25.0 * 25.0)
+        .collect();
+    assert_eq!(within, expected);
+
+    let nearest = |point| {
+        (0..positions.len())
+            .min_by(|&a, &b| {
+                distance_squared(positions[a], point)
+                    .partial_cmp(&distance_squared(positions[b], point))
+                    .unwrap()
+            })
+            .unwrap()
+    };
+    assert_eq!(index.nearest(point).unwrap().0, nearest(point));
+    // Far outside of the occupied cells, only the rings overlapping them get searched.
+    let far = (1e12, -3e11);
+    assert_eq!(index.nearest(far).unwrap().0, nearest(far));
+
+    let mut rect = index.in_rect((-20.0, -20.0), (15.0, 40.0));
+    rect.sort_unstable();
+    let expected: Vec<_> = (0..positions.len())
+        .filter(|&id| {
+            let (x, y) = positions[id];
+            (-20.0..=15.0).contains(&x) && (-20.0..=40.0).contains(&y)
+        })
+        .collect();
+    assert_eq!(rect, expected);
+}
@@ -0,0 +1,187 @@
+//! Turning orbits into polylines for rendering.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::{PI, TAU};
+
+use crate::{math, orbits::Object, time::Time, OrbitKind};
+
+/// How an orbit gets turned into a polyline.
+#[derive(Clone, Copy, Debug)]
+pub enum Tessellation {
+    /// A fixed number of points, evenly spaced in angle around the center of gravity.
+    /// Open orbits stop one degree before their asymptote.
+    Segments(usize),
+    /// Place points wherever the orbit curves, so that no line segment strays further
+    /// than `max_error` from the actual orbit. Choose `max_error` to be a fraction of a pixel
+    /// in the coordinates you draw in.
+    /// Closed orbits start and end at the current position of the object.
+    /// Open orbits (and the far side of very eccentric ellipses) stop at `max_radius`
+    /// from the center of gravity, which should usually be the edge of the screen.
+    Tolerance { max_error: f64, max_radius: f64 },
+}
+
+/// Minimum number of pieces a curve is split into before refining it. Without this
+/// a symmetric arc could have its midpoint on the chord and never be subdivided.
+const MIN_PIECES: usize = 16;
+/// Maximum number of times a piece is halved, so tiny tolerances don't explode.
+const MAX_DEPTH: u32 = 12;
+
+impl Object {
+    /// Tessellate the whole orbit. `start` is the angle of the object in its orbit at which
+    /// closed orbits start (and end), usually the angle of the object at the current time.
+    pub fn tessellate(&self, start: f64, tessellation: Tessellation) -> Vec<(f64, f64)> {
+        match tessellation {
+            Tessellation::Segments(segments) => self.segments(segments),
+            Tessellation::Tolerance {
+                max_error,
+                max_radius,
+            } => {
+                let (from, to) = match self.orbit.kind() {
+                    OrbitKind::Circle | OrbitKind::Ellipse => (start, start + TAU),
+                    OrbitKind::Parabola | OrbitKind::Hyperbola => {
+                        let limit = self.angle_limit(max_radius);
+                        (-limit, limit)
+                    }
+                };
+                self.subdivide(from, to, max_error, max_radius)
+            }
+        }
+    }
+
+    /// Tessellate the part of the orbit that the object moves along between the times `from` and `to`.
+    /// Used for drawing planned trajectories that end at a maneuver.
+    pub fn arc(
+        &self,
+        from: impl Into<Time>,
+        to: impl Into<Time>,
+        max_error: f64,
+        max_radius: f64,
+    ) -> Vec<(f64, f64)> {
+        let (from, to) = (from.into(), to.into());
+        let start = f64::from(self.angle_at(from));
+        let mut end = f64::from(self.angle_at(to));
+        match self.period() {
+            Some(period) => {
+                if to - from >= period {
+                    end = start + TAU;
+                } else if end < start {
+                    end += TAU;
+                }
+            }
+            None => {
+                end = end.min(self.angle_limit(max_radius));
+            }
+        }
+        self.subdivide(start, end, max_error, max_radius)
+    }
+
+    /// The angle in the orbit at which open orbits reach `max_radius`.
+    fn angle_limit(&self, max_radius: f64) -> f64 {
+        let p = f64::from(self.orbit.p);
+        let e = f64::from(self.orbit.epsilon);
+        // r = p / (1 + e * cos(angle))
+        // cos(angle) = (p / r - 1) / e
+        let cos = ((p / max_radius - 1.0) / e).max(-1.0);
+        // Even if `max_radius` is huge, never reach the asymptote itself.
+        let asymptote = math::acos((-1.0 / e).max(-1.0));
+        math::acos(cos).min(asymptote - 1e-6).min(PI - 1e-6)
+    }
+
+    /// Adaptively tessellate the orbit between the angles `from` and `to`.
+    fn subdivide(&self, from: f64, to: f64, max_error: f64, max_radius: f64) -> Vec<(f64, f64)> {
+        let step = (to - from) / MIN_PIECES as f64;
+        let mut points = vec![self.point_at(from)];
+        for i in 0..MIN_PIECES {
+            let a = from + step * i as f64;
+            self.refine(a, a + step, max_error, max_radius, MAX_DEPTH, &mut points);
+        }
+        points
+    }
+
+    /// Push the points between the angles `a` (exclusive) and `b` (inclusive).
+    fn refine(
+        &self,
+        a: f64,
+        b: f64,
+        max_error: f64,
+        max_radius: f64,
+        depth: u32,
+        points: &mut Vec<(f64, f64)>,
+    ) {
+        let start = self.point_at(a);
+        let end = self.point_at(b);
+        let mid = (a + b) / 2.0;
+        let mid_point = self.point_at(mid);
+        let far = |(x, y): (f64, f64)| x * x + y * y > max_radius * max_radius;
+        // Far away pieces are off screen, so no need to be precise there.
+        let off_screen = far(start) && far(end) && far(mid_point);
+        if depth > 0 && !off_screen && distance_to_line(mid_point, start, end) > max_error {
+            self.refine(a, mid, max_error, max_radius, depth - 1, points);
+            self.refine(mid, b, max_error, max_radius, depth - 1, points);
+        } else {
+            points.push(end);
+        }
+    }
+
+    /// The old fixed step tessellation.
+    fn segments(&self, segments: usize) -> Vec<(f64, f64)> {
+        let (start, range) = match self.orbit.kind() {
+            OrbitKind::Circle | OrbitKind::Ellipse => (0.0, TAU),
+            OrbitKind::Parabola | OrbitKind::Hyperbola => {
+                // 1/e = cos(angle)
+                let angle = math::acos(-1.0 / f64::from(self.orbit.epsilon));
+                let range = angle * 2.0;
+                // Subtract one degree so we don't render over infinity.
+                (-angle + TAU / 360.0, range - TAU / 180.0)
+            }
+        };
+        let step_size = range / segments as f64;
+        (0..segments)
+            .map(|i| self.point_at(step_size * (i + 1) as f64 + start))
+            .collect()
+    }
+}
+
+fn distance_to_line(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (px, py) = (point.0 - start.0, point.1 - start.1);
+    let len = math::sqrt(dx * dx + dy * dy);
+    if len == 0.0 {
+        math::sqrt(px * px + py * py)
+    } else {
+        (px * dy - py * dx).abs() / len
+    }
+}
+
+#[test]
+fn closed_orbits_start_at_object_and_open_orbits_stop_at_max_radius() {
+    use crate::Orbit;
+    use core::convert::TryInto as _;
+
+    let tessellation = Tessellation::Tolerance {
+        max_error: 0.1,
+        max_radius: 1000.0,
+    };
+    let ellipse = Orbit::from_pos_dir(
+        100.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.13.try_into().unwrap(),
+    );
+    let start = f64::from(ellipse.angle_at(500.0));
+    let points = ellipse.tessellate(start, tessellation);
+    let first = ellipse.point_at(start);
+    for &(x, y) in &[points[0], *points.last().unwrap()] {
+        assert!((x - first.0).abs() < 1e-9 && (y - first.1).abs() < 1e-9);
+    }
+
+    let hyperbola = Orbit::from_pos_dir(
+        100.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.2.try_into().unwrap(),
+    );
+    for (x, y) in hyperbola.tessellate(0.0, tessellation) {
+        assert!((x * x + y * y).sqrt() <= 1000.0 + 1e-6);
+    }
+}
@@ -0,0 +1,224 @@
+//! Time that stays precise no matter how long the game runs.
+//!
+//! A plain [f64] can only represent every multiple of `0.125` up to about `10¹⁵`, and positions
+//! computed from such a time start jittering long before that. [Time] keeps whole time units
+//! in an [i64] and only the fraction of the current unit in an [f64], so adding a small
+//! step to a huge time is always exact.
+
+use core::{
+    fmt::{self, Display},
+    num::ParseFloatError,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use crate::math;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Time {
+    whole: i64,
+    /// Always in `[0.0, 1.0)`.
+    frac: f64,
+}
+
+impl Time {
+    pub const ZERO: Self = Self {
+        whole: 0,
+        frac: 0.0,
+    };
+
+    pub fn new(whole: i64, frac: f64) -> Self {
+        let carry = math::floor(frac);
+        Self {
+            whole: whole + carry as i64,
+            frac: frac - carry,
+        }
+    }
+
+    pub fn whole(self) -> i64 {
+        self.whole
+    }
+
+    pub fn frac(self) -> f64 {
+        self.frac
+    }
+
+    /// Collapse into a single float, losing precision for large times.
+    pub fn as_f64(self) -> f64 {
+        self.whole as f64 + self.frac
+    }
+
+    /// The time since the start of the current period, in `[0.0, period)`.
+    /// The whole part is reduced on its own before the fraction is added,
+    /// so this is precise even for huge times.
+    pub fn rem_euclid(self, period: f64) -> f64 {
+        /// Whole times up to this convert to [f64] exactly.
+        const EXACT: i64 = 1 << 53;
+        let whole = if (-EXACT..=EXACT).contains(&self.whole) {
+            math::rem_euclid(self.whole as f64, period)
+        } else {
+            // Both halves convert exactly, and the remainder of an exact float is exact, too.
+            let high = (self.whole >> 32) as f64 * (1_u64 << 32) as f64;
+            let low = (self.whole & 0xffff_ffff) as f64;
+            math::rem_euclid(
+                math::rem_euclid(high, period) + math::rem_euclid(low, period),
+                period,
+            )
+        };
+        // Reducing twice can land exactly on `period` due to rounding.
+        let rem = math::rem_euclid(whole + self.frac, period);
+        if rem >= period {
+            0.0
+        } else {
+            rem
+        }
+    }
+}
+
+impl From<f64> for Time {
+    fn from(t: f64) -> Self {
+        Self::new(0, t)
+    }
+}
+
+impl Add for Time {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.whole + rhs.whole, self.frac + rhs.frac)
+    }
+}
+
+impl Add<f64> for Time {
+    type Output = Self;
+    fn add(self, rhs: f64) -> Self {
+        self + Self::from(rhs)
+    }
+}
+
+impl AddAssign for Time {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl AddAssign<f64> for Time {
+    fn add_assign(&mut self, rhs: f64) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign<f64> for Time {
+    fn sub_assign(&mut self, rhs: f64) {
+        *self += -rhs;
+    }
+}
+
+impl Neg for Time {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.whole, -self.frac)
+    }
+}
+
+/// The difference between two times. Precise as long as the two times are close to each other.
+impl Sub for Time {
+    type Output = f64;
+    fn sub(self, rhs: Self) -> f64 {
+        (self.whole - rhs.whole) as f64 + (self.frac - rhs.frac)
+    }
+}
+
+/// Formats as `whole+frac`, e.g. `123456789+0.25`.
+impl Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{}", self.whole, self.frac)
+    }
+}
+
+/// Parses the [Display] format, or a plain float.
+impl FromStr for Time {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((whole, frac)) = s.rsplit_once('+') {
+            // Floats in exponent notation like `1e+5` contain a `+`, too.
+            if let Ok(whole) = whole.parse() {
+                return Ok(Self::new(whole, frac.parse()?));
+            }
+        }
+        s.parse().map(f64::into)
+    }
+}
+
+#[test]
+fn roundtrip() {
+    for &time in &[
+        Time::ZERO,
+        Time::from(-3.5),
+        Time::new(1_000_000_000_000_000, 0.3),
+    ] {
+        assert_eq!(time.to_string().parse::<Time>().unwrap(), time);
+    }
+    assert_eq!("1e+3".parse::<Time>().unwrap(), Time::from(1000.0));
+    assert_eq!("12.5".parse::<Time>().unwrap(), Time::new(12, 0.5));
+    assert_eq!(-Time::new(5, 0.25), Time::new(-6, 0.75));
+}
+
+#[test]
+fn rem_euclid_of_huge_times() {
+    for &whole in &[(1 << 60) + 1, -(1 << 60) - 3, i64::MAX, i64::MIN, 12] {
+        let expected = whole.rem_euclid(7) as f64 + 0.5;
+        assert_eq!(Time::new(whole, 0.5).rem_euclid(7.0), expected, "{whole}");
+    }
+}
+
+#[test]
+fn positions_stable_after_huge_times() {
+    use crate::{orbits::Object, Orbit};
+    use core::convert::TryInto as _;
+
+    let circle = Object {
+        angle: 0.0.try_into().unwrap(),
+        t: Time::ZERO,
+        orbit: Orbit::circular(200.0.try_into().unwrap()),
+    };
+    let speed = 1.0 / 200.0_f64.sqrt();
+    for &start in &[
+        Time::ZERO,
+        Time::new(1_000_000_000, 0.0),
+        Time::new(1_000_000_000_000_000, 0.3),
+    ] {
+        // Moving in steps of a frame must move the same distance no matter how late it is.
+        let mut t = start;
+        let mut prev = circle.position_at(t);
+        for _ in 0..100 {
+            t += 10.0;
+            let pos = circle.position_at(t);
+            let distance = (pos.0 - prev.0).hypot(pos.1 - prev.1);
+            let expected = 2.0 * 200.0 * (speed * 10.0 / 200.0 / 2.0).sin();
+            assert!(
+                (distance - expected).abs() < 1e-9,
+                "{}: {} != {}",
+                t,
+                distance,
+                expected
+            );
+            prev = pos;
+        }
+    }
+
+    // Rebasing an object that started a long time ago does not move it.
+    let mut ellipse = Orbit::from_pos_dir(
+        100.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.12.try_into().unwrap(),
+    );
+    ellipse.t += Time::new(-1_000_000_000, 0.75);
+    let now = Time::new(1_000_000_000, 0.5);
+    let before = ellipse.position_at(now);
+    assert!(ellipse.rebase());
+    assert!(ellipse.t >= Time::ZERO && ellipse.t.as_f64() < ellipse.period().unwrap());
+    let after = ellipse.position_at(now);
+    assert!((before.0 - after.0).abs() < 1e-6 && (before.1 - after.1).abs() < 1e-6);
+}
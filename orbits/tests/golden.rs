@@ -0,0 +1,79 @@
+//! Positions computed by this crate, down to the last bit.
+//!
+//! The expected values were computed with the default features. Running this with
+//! `--no-default-features` checks that the `no_std` build computes exactly the same orbits.
+
+use std::convert::TryInto as _;
+
+use ::orbits::{Orbit, Orbits, Time};
+
+fn orbits() -> Orbits {
+    let mut orbits = Orbits::default();
+    // Removed again below, so that ids and batch indices differ.
+    let removed = orbits.insert("angle=0 t=0 p=10 epsilon=0".parse().unwrap());
+    orbits.insert(Orbit::from_pos_dir(
+        150.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        0.0.try_into().unwrap(),
+        (1.0_f64 / 150.0).sqrt().try_into().unwrap(),
+    ));
+    orbits.insert(Orbit::from_pos_dir(
+        (-120.0).try_into().unwrap(),
+        35.5.try_into().unwrap(),
+        0.01.try_into().unwrap(),
+        (-0.07).try_into().unwrap(),
+    ));
+    orbits.insert("angle=0.5 t=-300+0.25 p=200 epsilon=1".parse().unwrap());
+    orbits.insert(Orbit::from_pos_dir(
+        80.0.try_into().unwrap(),
+        (-20.0).try_into().unwrap(),
+        0.1.try_into().unwrap(),
+        0.3.try_into().unwrap(),
+    ));
+    orbits.remove(removed);
+    orbits
+}
+
+fn times() -> [Time; 4] {
+    [
+        Time::ZERO,
+        Time::from(123.456),
+        Time::new(-2_000, 0.5),
+        Time::new(1_000_000_000_000, 0.125),
+    ]
+}
+
+/// Bits of the x and y coordinates of every object at every one of [times].
+const EXPECTED: [[u64; 2]; 16] = [
+    [0x4062c00000000000, 0x0000000000000000],
+    [0x4062b52a6f4b3a6f, 0x40242525ecf6ec29],
+    [0x4051658ed64ca436, 0xc0609c3abde2d62a],
+    [0x40602177bc095a60, 0xc0531ddf4bfc1556],
+    [0xc05e000000000000, 0x4041c00000000000],
+    [0xc05d9253e22bf8e2, 0x403aba42e0067153],
+    [0xc048e5e1400f9356, 0x405d31d66d863f6d],
+    [0x4010663026d639be, 0x4058c666136eb0c4],
+    [0x4059fd6edbbdfdf3, 0x40225d2008548b49],
+    [0x40589338924debac, 0x40396fe3d8a00cec],
+    [0x4054d85534f006f6, 0xc06aae9a6942cae6],
+    [0xc1d025ec4e955550, 0xc1c19ea2cb3ae747],
+    [0x4054000000000001, 0xc033fffffffffff7],
+    [0x4056d2c4bc4f4e57, 0x403123feac0ce2ea],
+    [0xc0659a29403df4c4, 0xc0810769e091f49c],
+    [0x4228a58536601d1a, 0x424f711cf316e5ca],
+];
+
+#[test]
+fn positions() {
+    let orbits = orbits();
+    let mut expected = EXPECTED.iter();
+    for (id, object) in orbits.iter() {
+        for t in times() {
+            let &[x, y] = expected.next().unwrap();
+            let (x, y) = (f64::from_bits(x), f64::from_bits(y));
+            assert_eq!(object.position_at(t), (x, y), "object {id} at {t}");
+            let idx = orbits.index(id).unwrap();
+            assert_eq!(orbits.batch().position(idx, t), (x, y), "batch {id} at {t}");
+        }
+    }
+}
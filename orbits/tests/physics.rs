@@ -0,0 +1,306 @@
+//! Checks the orbital mechanics against physics instead of against themselves.
+//!
+//! Every property is checked on a few hundred randomly generated (but reproducible) starting states
+//! for every [OrbitKind], and a handful of well-known orbits are checked against values computed by hand.
+
+use std::{
+    convert::TryInto as _,
+    f64::consts::{FRAC_PI_2, PI, TAU},
+};
+
+use ::orbits::{batch::Batch, orbits::Object, Orbit, OrbitKind, Time};
+
+/// Number of random starting states per orbit kind.
+const SAMPLES: usize = 300;
+
+/// Small xorshift generator, so failures are reproducible without pulling in a dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next() * (max - min)
+    }
+}
+
+type State = ((f64, f64), (f64, f64));
+
+/// A random counterclockwise starting state that results in an orbit of the given kind.
+fn sample(rng: &mut Rng, kind: OrbitKind) -> State {
+    let r = rng.range(50.0, 250.0);
+    let phi = rng.range(-PI, PI);
+    let circular = (1.0 / r).sqrt();
+    // Angle between the velocity and the tangent of a circle around the center of gravity.
+    let mut flight_angle = rng.range(-1.2, 1.2);
+    let speed = match kind {
+        OrbitKind::Circle => {
+            flight_angle = 0.0;
+            circular
+        }
+        OrbitKind::Ellipse => circular * rng.range(0.3, 1.35),
+        OrbitKind::Parabola => (2.0 / r).sqrt(),
+        OrbitKind::Hyperbola => circular * rng.range(1.5, 3.0),
+    };
+    let dir = phi + FRAC_PI_2 - flight_angle;
+    (
+        (r * phi.cos(), r * phi.sin()),
+        (speed * dir.cos(), speed * dir.sin()),
+    )
+}
+
+fn from_state(((x, y), (dx, dy)): State) -> Object {
+    Orbit::from_pos_dir(
+        x.try_into().unwrap(),
+        y.try_into().unwrap(),
+        dx.try_into().unwrap(),
+        dy.try_into().unwrap(),
+    )
+}
+
+fn energy(((x, y), (dx, dy)): State) -> f64 {
+    (dx * dx + dy * dy) / 2.0 - 1.0 / x.hypot(y)
+}
+
+fn angular_momentum(((x, y), (dx, dy)): State) -> f64 {
+    x * dy - y * dx
+}
+
+fn state_at(object: &Object, t: impl Into<Time> + Copy) -> State {
+    (object.position_at(t), object.velocity_at(t))
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Integrate the two body problem numerically with fourth order Runge-Kutta.
+fn integrate(state: State, duration: f64, steps: usize) -> State {
+    let dt = duration / steps as f64;
+    let derivative = |((x, y), (dx, dy)): State| -> State {
+        let r3 = x.hypot(y).powi(3);
+        ((dx, dy), (-x / r3, -y / r3))
+    };
+    let add = |((x, y), (dx, dy)): State, ((a, b), (da, db)): State, f: f64| -> State {
+        ((x + a * f, y + b * f), (dx + da * f, dy + db * f))
+    };
+    let mut state = state;
+    for _ in 0..steps {
+        let k1 = derivative(state);
+        let k2 = derivative(add(state, k1, dt / 2.0));
+        let k3 = derivative(add(state, k2, dt / 2.0));
+        let k4 = derivative(add(state, k3, dt));
+        state = add(state, k1, dt / 6.0);
+        state = add(state, k2, dt / 3.0);
+        state = add(state, k3, dt / 3.0);
+        state = add(state, k4, dt / 6.0);
+    }
+    state
+}
+
+fn for_all_kinds(mut check: impl FnMut(OrbitKind, State)) {
+    let mut rng = Rng(0x5eed_0b17);
+    for &kind in &[
+        OrbitKind::Circle,
+        OrbitKind::Ellipse,
+        OrbitKind::Parabola,
+        OrbitKind::Hyperbola,
+    ] {
+        for _ in 0..SAMPLES {
+            check(kind, sample(&mut rng, kind));
+        }
+    }
+}
+
+#[test]
+fn classification() {
+    for_all_kinds(|kind, state| {
+        let object = from_state(state);
+        assert_eq!(
+            format!("{:?}", object.orbit.kind()),
+            format!("{:?}", kind),
+            "{:?}",
+            state
+        );
+    });
+}
+
+#[test]
+fn roundtrip() {
+    for_all_kinds(|kind, state| {
+        let object = from_state(state);
+        let (pos, vel) = state_at(&object, 0.0);
+        let r = state.0 .0.hypot(state.0 .1);
+        let speed = state.1 .0.hypot(state.1 .1);
+        assert!(
+            distance(pos, state.0) < r * 1e-6,
+            "{:?} {:?}: position {:?}",
+            kind,
+            state,
+            pos
+        );
+        assert!(
+            distance(vel, state.1) < speed * 1e-6,
+            "{:?} {:?}: velocity {:?}",
+            kind,
+            state,
+            vel
+        );
+    });
+}
+
+#[test]
+fn conservation() {
+    for_all_kinds(|kind, state| {
+        let object = from_state(state);
+        let energy0 = energy(state);
+        let momentum0 = angular_momentum(state);
+        for &t in &[1.0, 100.0, 12_345.6, 1e6] {
+            let state = state_at(&object, t);
+            // Parabolas have zero energy, so compare relative to the kinetic energy.
+            let scale = (state.1 .0 * state.1 .0 + state.1 .1 * state.1 .1).max(1e-12);
+            assert!(
+                (energy(state) - energy0).abs() / scale < 1e-6,
+                "{:?} at {}: energy {} != {}",
+                kind,
+                t,
+                energy(state),
+                energy0
+            );
+            assert!(
+                (angular_momentum(state) - momentum0).abs() / momentum0 < 1e-6,
+                "{:?} at {}: angular momentum {} != {}",
+                kind,
+                t,
+                angular_momentum(state),
+                momentum0
+            );
+        }
+    });
+}
+
+#[test]
+fn matches_numerical_integration() {
+    let mut checked = 0;
+    for_all_kinds(|kind, state| {
+        // Numerical integration is slow, so only check every tenth sample.
+        checked += 1;
+        if checked % 10 != 0 {
+            return;
+        }
+        let object = from_state(state);
+        let duration = 2_000.0;
+        let (expected, _) = integrate(state, duration, 4_000);
+        let actual = object.position_at(duration);
+        let r = expected.0.hypot(expected.1);
+        assert!(
+            distance(actual, expected) < r * 1e-4,
+            "{:?} {:?}: {:?} != {:?}",
+            kind,
+            state,
+            actual,
+            expected
+        );
+    });
+}
+
+#[test]
+fn batch_matches_objects() {
+    let mut objects = Vec::new();
+    for_all_kinds(|_, state| objects.push(from_state(state)));
+    let mut batch = Batch::default();
+    for object in &objects {
+        batch.push(object);
+    }
+    for &t in &[0.0, 500.0, 1e6] {
+        for (idx, object) in objects.iter().enumerate() {
+            let expected = object.position_at(t);
+            let actual = batch.position(idx, t);
+            let r = expected.0.hypot(expected.1);
+            assert!(
+                distance(expected, actual) < r * 1e-9,
+                "{} at {}: {:?} != {:?}",
+                idx,
+                t,
+                expected,
+                actual
+            );
+        }
+    }
+}
+
+#[test]
+fn clockwise_velocities_are_mirrored() {
+    let mut rng = Rng(42);
+    for _ in 0..SAMPLES {
+        let (pos, (dx, dy)) = sample(&mut rng, OrbitKind::Ellipse);
+        // Mirror the velocity along the line to the center of gravity.
+        let (rx, ry) = (pos.0 / pos.0.hypot(pos.1), pos.1 / pos.0.hypot(pos.1));
+        let radial = dx * rx + dy * ry;
+        let clockwise = (pos, (2.0 * radial * rx - dx, 2.0 * radial * ry - dy));
+        let object = from_state(clockwise);
+        let state = state_at(&object, 0.0);
+        assert!(distance(state.0, pos) < 1e-6);
+        assert!((energy(state) - energy(clockwise)).abs() < 1e-9);
+        assert!((angular_momentum(state) + angular_momentum(clockwise)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn circle() {
+    let object = from_state(((200.0, 0.0), (0.0, (1.0_f64 / 200.0).sqrt())));
+    assert!(matches!(object.orbit.kind(), OrbitKind::Circle));
+    let period = TAU * 200.0_f64.powf(1.5);
+    assert!((object.period().unwrap() - period).abs() < 1e-6);
+    assert!(distance(object.position_at(period / 2.0), (-200.0, 0.0)) < 1e-6);
+    assert!(distance(object.position_at(period / 4.0), (0.0, 200.0)) < 1e-6);
+}
+
+#[test]
+fn ellipse() {
+    // r = 100, v = 0.12 perpendicular: h = 12, p = h² = 144, e = p / r - 1 = 0.44
+    let object = from_state(((100.0, 0.0), (0.0, 0.12)));
+    assert!((f64::from(object.orbit.p) - 144.0).abs() < 1e-9);
+    assert!((f64::from(object.orbit.epsilon) - 0.44).abs() < 1e-9);
+    let a = 144.0 / (1.0 - 0.44 * 0.44);
+    let period = TAU * a * a.sqrt();
+    assert!((object.period().unwrap() - period).abs() < 1e-6);
+    // Starting at the periapsis, half an orbit later it is at the apoapsis.
+    let apoapsis = 144.0 / (1.0 - 0.44);
+    assert!(distance(object.position_at(period / 2.0), (-apoapsis, 0.0)) < 1e-3);
+    assert!(distance(object.apoapsis().unwrap(), (-apoapsis, 0.0)) < 1e-9);
+}
+
+#[test]
+fn parabola() {
+    // r = 100, escape velocity perpendicular: p = h² = 200
+    let object = from_state(((100.0, 0.0), (0.0, 0.02_f64.sqrt())));
+    assert!(matches!(object.orbit.kind(), OrbitKind::Parabola));
+    // Barker's equation for a true anomaly of 90°: t = sqrt(p³) / 2 * (1 + 1 / 3)
+    let t = (200.0_f64).powf(1.5) / 2.0 * (4.0 / 3.0);
+    assert!(distance(object.position_at(t), (0.0, 200.0)) < 1e-3);
+    assert!(distance(object.position_at(-t), (0.0, -200.0)) < 1e-3);
+}
+
+#[test]
+fn hyperbola() {
+    // r = 100, v = 0.2 perpendicular: h = 20, p = 400, e = p / r - 1 = 3
+    let object = from_state(((100.0, 0.0), (0.0, 0.2)));
+    assert!(matches!(object.orbit.kind(), OrbitKind::Hyperbola));
+    assert!((f64::from(object.orbit.epsilon) - 3.0).abs() < 1e-9);
+    // Far away the speed approaches sqrt(v² - 2 / r)
+    let (dx, dy) = object.velocity_at(1e7);
+    assert!((dx.hypot(dy) - 0.02_f64.sqrt()).abs() < 1e-3);
+    let [incoming, outgoing] = object.asymptotes().unwrap();
+    let angle = (-1.0_f64 / 3.0).acos();
+    assert!(distance(outgoing, (angle.cos(), angle.sin())) < 1e-9);
+    assert!(distance(incoming, (angle.cos(), -angle.sin())) < 1e-9);
+    // The object escapes along the outgoing asymptote.
+    let (x, y) = object.direction_at(1e9);
+    assert!(distance((x, y), outgoing) < 1e-3);
+}
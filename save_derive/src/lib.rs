@@ -0,0 +1,236 @@
+//! `#[derive(Save)]` for the `Save` trait of the game's save module.
+//!
+//! Every field is saved below the key of the value, with the field's name (or index, for tuple
+//! structs) as the next path segment. Enums save the name of their variant at `<key>/variant`
+//! and the fields of that variant below `<key>/<Variant>/`. Saving a variant removes everything
+//! saved for the other variants.
+//!
+//! Type attributes:
+//! * `#[save(crate = path)]`: the crate whose `save` module has the `Save` trait, `crate` by default.
+//!   Needed where `crate::save` is not that module, e.g. `#[save(crate = ::solar_sailors)]` in a binary
+//!   that doesn't `use solar_sailors::save`.
+//!
+//! Field attributes:
+//! * `#[save(skip)]`: the field is never saved or loaded.
+//! * `#[save(default = expr)]`: when loading, the field is reset to `expr` first, so it ends up as
+//!   `expr` if nothing was saved for it. Enum variants that are loaded while the value is another
+//!   variant get created with these defaults, and `Default::default()` for all other fields.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Expr, Fields,
+    Ident, Index, Member, Path,
+};
+
+#[proc_macro_derive(Save, attributes(save))]
+pub fn derive_save(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    member: Member,
+    /// Used for the field in patterns, so it doesn't clash with anything else.
+    binding: Ident,
+    /// Path segment below the key of the value.
+    name: String,
+    skip: bool,
+    default: Option<Expr>,
+}
+
+impl Field {
+    /// Value of the field when creating it from nothing.
+    fn default(&self) -> TokenStream {
+        match &self.default {
+            Some(default) => quote!(#default),
+            None => quote!(::core::default::Default::default()),
+        }
+    }
+}
+
+fn fields(fields: &Fields) -> syn::Result<Vec<Field>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let (member, name) = match &field.ident {
+                Some(ident) => (Member::Named(ident.clone()), ident.unraw().to_string()),
+                None => (Member::Unnamed(Index::from(i)), i.to_string()),
+            };
+            let mut skip = false;
+            let mut default = None;
+            for attr in &field.attrs {
+                if !attr.path().is_ident("save") {
+                    continue;
+                }
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        skip = true;
+                    } else if meta.path.is_ident("default") {
+                        default = Some(meta.value()?.parse()?);
+                    } else {
+                        return Err(meta.error("expected `skip` or `default = ...`"));
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(Field {
+                member,
+                binding: format_ident!("field_{i}"),
+                name,
+                skip,
+                default,
+            })
+        })
+        .collect()
+}
+
+/// Path of the crate given with `#[save(crate = path)]`, or `crate`.
+fn krate(attrs: &[Attribute]) -> syn::Result<Path> {
+    let mut krate = parse_quote!(crate);
+    for attr in attrs {
+        if !attr.path().is_ident("save") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                krate = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate = ...`"))
+            }
+        })?;
+    }
+    Ok(krate)
+}
+
+/// Save and load the fields bound to their `binding`s, with keys below `prefix`.
+fn save_and_load(fields: &[Field], prefix: &str, krate: &Path) -> (TokenStream, TokenStream) {
+    let mut save = TokenStream::new();
+    let mut load = TokenStream::new();
+    for field in fields {
+        let binding = &field.binding;
+        if let Some(default) = &field.default {
+            load.extend(quote!(*#binding = #default;));
+        }
+        if field.skip {
+            continue;
+        }
+        let key = format!("{{}}/{prefix}{}", field.name);
+        save.extend(quote!(#krate::save::Save::save(#binding, ::core::format_args!(#key, key));));
+        load.extend(quote!(#krate::save::Save::load(#binding, ::core::format_args!(#key, key));));
+    }
+    (save, load)
+}
+
+/// Binds all fields that are saved or have a default to their `binding`s.
+fn pattern(fields: &[Field]) -> TokenStream {
+    let bound = fields
+        .iter()
+        .filter(|field| !field.skip || field.default.is_some());
+    let members = bound.clone().map(|field| &field.member);
+    let bindings = bound.map(|field| &field.binding);
+    quote!({ #(#members: #bindings,)* .. })
+}
+
+fn derive(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let krate = krate(&input.attrs)?;
+    let (save, load) = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let pattern = pattern(&fields);
+            let (save, load) = save_and_load(&fields, "", &krate);
+            (
+                quote!(let Self #pattern = self; #save),
+                quote!(let Self #pattern = self; #load),
+            )
+        }
+        Data::Enum(data) => {
+            let mut save = TokenStream::new();
+            let mut load = TokenStream::new();
+            let names: Vec<_> = data
+                .variants
+                .iter()
+                .map(|variant| variant.ident.unraw().to_string())
+                .collect();
+            for (variant, name) in data.variants.iter().zip(&names) {
+                let ident = &variant.ident;
+                let fields = fields(&variant.fields)?;
+                let pattern = pattern(&fields);
+                let (save_fields, load_fields) =
+                    save_and_load(&fields, &format!("{name}/"), &krate);
+                // Fields of the other variants would be loaded again after switching back to them.
+                let others = names
+                    .iter()
+                    .filter(|other| *other != name)
+                    .map(|other| format!("{{}}/{other}"));
+                save.extend(quote!(Self::#ident #pattern => {
+                    #krate::save::save(::core::format_args!("{}/variant", key), #name);
+                    #(#krate::save::remove(::core::format_args!(#others, key));)*
+                    #save_fields
+                }));
+                let members = fields.iter().map(|field| &field.member);
+                let defaults = fields.iter().map(Field::default);
+                load.extend(quote!(#name => {
+                    if !::core::matches!(self, Self::#ident { .. }) {
+                        *self = Self::#ident { #(#members: #defaults,)* };
+                    }
+                    if let Self::#ident #pattern = self {
+                        #load_fields
+                    }
+                }));
+            }
+            (
+                quote!(match self { #save }),
+                quote!(
+                    let ::core::option::Option::Some(variant) =
+                        #krate::save::load::<::std::string::String>(::core::format_args!("{}/variant", key))
+                    else {
+                        return;
+                    };
+                    match variant.as_str() {
+                        #load
+                        // Unknown variants, e.g. of a newer version of the game, keep the current value.
+                        _ => {}
+                    }
+                ),
+            )
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "`Save` can't be derived for unions",
+            ))
+        }
+    };
+
+    let params: Vec<_> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: #krate::save::Save));
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::save::Save for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn save(&self, key: impl ::core::fmt::Display) {
+                #save
+            }
+
+            #[allow(unused_variables, irrefutable_let_patterns)]
+            fn load(&mut self, key: impl ::core::fmt::Display) {
+                #load
+            }
+        }
+    })
+}
@@ -0,0 +1,127 @@
+//! Look into and fix save games without starting the game.
+//!
+//! Works on the same storage as the game, so `--dir` is only needed for save games that were
+//! copied somewhere else, e.g. from a bug report.
+//!
+//! Looking at a slot opens the storage read only, so it is shown exactly as it is stored and
+//! nothing gets written. Changing it migrates it to the newest schema version first, just like
+//! starting the game would.
+
+use std::{env, process::ExitCode};
+
+use solar_sailors::save::{
+    self, backend, inspect, slots,
+    storage::{self, Transaction},
+};
+
+const USAGE: &str = "\
+usage: solar-save [--dir <path>] [--slot <name>] <command>
+
+commands:
+    slots              list all slots
+    keys               list the keys of the last successful frame
+    print              print the last successful frame as `key=value` lines
+    diff               compare the frame before the last successful one with it
+    set <key> <value>  migrate the slot and change a key in a new frame
+    delete <key>       migrate the slot and remove a key and all keys below it in a new frame
+    validate           check the `odd` marker, the metadata, the collections and all other values";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<(), String> {
+    let mut slot = slots::DEFAULT.to_owned();
+    // The storage of the game, unless `--dir` is given.
+    let mut backend = backend::platform();
+    loop {
+        match &args[..] {
+            [flag, name, ..] if flag == "--slot" => slot = name.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            [flag, dir, ..] if flag == "--dir" => backend = Box::new(backend::FileSystem::new(dir)),
+            _ => break,
+        }
+        args.drain(..2);
+    }
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    if matches!(args[..], ["set" | "delete", ..]) {
+        save::set_backend(backend);
+    } else {
+        storage::set_read_only_backend(backend);
+    }
+    if args == ["slots"] {
+        for info in slots::list() {
+            let last_played = info.last_played.unwrap_or_default();
+            println!(
+                "{}: {} segments, played for {:.0}s, last at {last_played:.0} (unix time)",
+                info.name,
+                info.thumbnail.len(),
+                info.play_time,
+            );
+        }
+        return Ok(());
+    }
+    let known = matches!(
+        args[..],
+        ["keys" | "print" | "diff" | "validate"] | ["set", _, _] | ["delete", _]
+    );
+    if !known {
+        return Err(USAGE.to_owned());
+    }
+    // Everything else looks at a single slot, which must not be created by selecting it.
+    if !slots::list().iter().any(|info| info.name == slot) {
+        return Err(slots::SlotError::Missing(slot).to_string());
+    }
+    slots::select(&slot).map_err(|err| err.to_string())?;
+    let (committed, previous) = inspect::frames().unwrap_or_default();
+    match args[..] {
+        ["keys"] => {
+            for key in committed.keys() {
+                println!("{key}");
+            }
+        }
+        ["print"] => print!("{}", save::export()),
+        ["diff"] => {
+            let mut keys: Vec<_> = previous.keys().chain(committed.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                match (previous.get(key), committed.get(key)) {
+                    (Some(old), Some(new)) if old != new => println!("~ {key}: {old:?} -> {new:?}"),
+                    (Some(_), Some(_)) => {}
+                    (Some(old), None) => println!("- {key}: {old:?}"),
+                    (None, Some(new)) => println!("+ {key}: {new:?}"),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        ["set", key, value] => commit(|| save::save(key, value))?,
+        ["delete", key] => commit(|| save::remove(key))?,
+        ["validate"] => {
+            let problems = inspect::validate();
+            for problem in &problems {
+                println!("{problem}");
+            }
+            if !problems.is_empty() {
+                return Err(format!("{} problems in slot `{slot}`", problems.len()));
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Apply `change` in a new frame, just like the game would, after migrating the slot.
+fn commit(change: impl FnOnce()) -> Result<(), String> {
+    let mut transaction = Transaction::start();
+    change();
+    transaction
+        .commit()
+        .map_err(|err| format!("could not write the save: {err}"))
+}
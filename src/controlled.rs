@@ -2,20 +2,24 @@ use std::ops::{Add, Sub};
 
 use macroquad::prelude::*;
 
-use crate::datastructures::{Reader, Sensor, SetGet};
+use crate::{
+    datastructures::{Reader, Sensor},
+    save::{Save, Saveable},
+};
 
 pub(crate) struct ControlledRange<T = f32> {
     pub min: T,
-    pub value: Sensor<T>,
+    pub value: Sensor<Saveable<T>>,
     pub max: T,
     speed: T,
 }
 
-impl<T: SetGet<Val = T> + Add<Output = T> + Sub<Output = T> + Copy + From<f32> + PartialOrd>
+impl<T: Save + Add<Output = T> + Sub<Output = T> + Copy + From<f32> + PartialOrd>
     ControlledRange<T>
 {
-    pub fn new(min: T, max: T) -> (Self, Reader<T>) {
-        let (value, reader) = Sensor::new(max);
+    /// Starts out at `max`, unless a value was saved at `key`.
+    pub fn new(min: T, max: T, key: impl ToString) -> (Self, Reader<Saveable<T>>) {
+        let (value, reader) = Sensor::new(Saveable::new(max, key));
         (
             Self {
                 value,
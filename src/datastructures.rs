@@ -66,11 +66,19 @@ impl<T: Copy> SetGet for T {
     }
 }
 
-#[derive(Clone)]
 pub struct Reader<T> {
     output: Weak<RefCell<T>>,
 }
 
+// Not derived, so `T` doesn't need to be `Clone`.
+impl<T> Clone for Reader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+        }
+    }
+}
+
 pub trait SetGet {
     type Val;
     fn get(&self) -> Self::Val;
@@ -0,0 +1,4 @@
+//! The save game format, shared by the game and the `solar-save` tool.
+
+pub mod datastructures;
+pub mod save;
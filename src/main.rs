@@ -1,38 +1,16 @@
-use std::{
-    collections::HashMap,
-    convert::{TryFrom as _, TryInto as _},
-    f32::consts::{FRAC_PI_2, FRAC_PI_3, PI},
-    sync::{Arc, Mutex},
-};
-
-use macroquad::prelude::{
-    coroutines::{start_coroutine, wait_seconds},
-    *,
-};
-use save::Saveable;
-use ship::{Gauge, GaugeHandle, Segment, SpaceShip};
+use macroquad::prelude::*;
+use ship::SpaceShip;
+use solar_sailors::{datastructures, save};
 use stars::Stars;
 
-use crate::{
-    datastructures::SetGet,
-    player::Player,
-    ship::{Attachement, Map, Sail},
-};
+use crate::{datastructures::Reader, player::Player};
 
 mod controlled;
-mod datastructures;
 mod orbits;
 mod player;
-mod save;
 mod ship;
 mod stars;
 
-use orbits::{
-    orbits::Object,
-    typed_floats::{NonNaNFinite, StrictlyPositiveFinite},
-    Orbit,
-};
-
 #[cfg(target_arch = "wasm32")]
 use web_sys::*;
 
@@ -46,165 +24,332 @@ fn window_conf() -> Conf {
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut player = Player::new((0, -1), 3);
-    let mut stars = Stars::default();
-    let orbit_render_target = render_target(1024, 1024);
-    let map = Map {
-        texture: orbit_render_target.texture.clone(),
-        zoom: Saveable::new(0.5, "map_zoom"),
-        small_zoom: 1.0,
-    };
-    let mut orbits = orbits::Orbits::load();
-    orbits.insert(Object {
-        angle: NonNaNFinite::<f64>::try_from(0.0).unwrap(),
-        t: 0.0.try_into().unwrap(),
-        orbit: Orbit::circular(200.0_f64.try_into().unwrap()),
-    });
-    // Find a parabolic orbit
-    let mut dy = 0.141.try_into().unwrap();
-    loop {
-        let object = Orbit::from_pos_dir(
-            100.0.try_into().unwrap(),
-            0.0.try_into().unwrap(),
-            0.0.try_into().unwrap(),
-            dy,
-        );
-        let diff = StrictlyPositiveFinite::<f64>::try_from(1.0).unwrap() - object.orbit.epsilon;
-        let diff = diff * StrictlyPositiveFinite::<f64>::try_from(0.01).unwrap();
-        if diff.abs() < 1e-6 {
-            orbits.insert(object);
-            break;
+    // Play without touching the save game, e.g. for testing.
+    if std::env::var_os("SOLAR_SAILORS_NO_SAVE").is_some() {
+        save::set_backend(save::backend::Memory::default());
+    }
+    // Problems with the settings are shown to the player, who may not see the log.
+    let mut problems = Vec::new();
+    if let Some(slot) = std::env::var_os("SOLAR_SAILORS_SLOT") {
+        let slot = slot.to_string_lossy();
+        if let Err(err) = save::slots::select(&slot) {
+            problems.push(format!(
+                "SOLAR_SAILORS_SLOT: {err}, playing slot `{}`",
+                save::slots::active()
+            ));
+        }
+    }
+    // Seconds between autosaves.
+    if let Some(interval) = std::env::var_os("SOLAR_SAILORS_AUTOSAVE") {
+        match interval.to_string_lossy().parse::<f64>() {
+            Ok(interval) if interval.is_finite() && interval >= 0.0 => {
+                save::set_autosave_interval(interval)
+            }
+            _ => problems.push(format!(
+                "SOLAR_SAILORS_AUTOSAVE: expected seconds, found {interval:?}, using the default"
+            )),
         }
-        dy += NonNaNFinite::try_from(diff).unwrap();
     }
+    let orbit_render_target = render_target(1024, 1024);
+    // A message for the player and when it was shown.
+    let mut notice: Option<(String, f64)> =
+        (!problems.is_empty()).then(|| (problems.join("; "), get_time()));
+    // Everything gets loaded again after rolling back or starting a new game.
+    loop {
+        let mut player = Player::new((0, -1), 3);
+        let mut stars = Stars::default();
+        let mut orbits = orbits::Orbits::load(include_str!("../assets/start.orbits"));
+        let (mut ship, parts) = SpaceShip::load(&orbit_render_target.texture, orbits.sunlight());
+        stars
+            .sails
+            .extend(parts.sails.iter().map(|sail| sail.rope_positions.clone()));
+        stars.sunlight = Some(orbits.sunlight());
+
+        save::slots::set_thumbnail(ship.grid.keys().map(|pos| (pos.x, pos.y)));
+
+        let mut window = GameWindow::Ship;
+        let mut menu = None;
+        let mut restart = None;
+        let exit = save::transaction_loop(|| {
+            // Logic
+            if !(cfg!(debug_assertions) && is_key_down(KeyCode::Space)) {
+                stars.update();
+                ship.update();
+                orbits.update();
+                player.update(&mut ship.grid);
+            }
 
-    let sail_width = 100.0;
-    let (
-        sail,
-        ship::SailParameters {
-            rope_positions,
-            force,
-            current_angle,
-            left_rope,
-            right_rope,
-        },
-    ) = Sail::new(200.0, 200.0, sail_width, 20.0, -FRAC_PI_3);
-    stars.sails.push(rope_positions);
-    let mut ship = SpaceShip {
-        pos: Vec2::new(0.0, 0.0),
-        grid: HashMap::default(),
-    };
-    let mut attachements: [Option<Box<dyn Attachement>>; 6] = Default::default();
-    attachements[1] = Some(Box::new(sail));
-    attachements[4] = Some(Box::new(map));
-    ship.grid.insert(
-        (0, 0).into(),
-        Segment {
-            content: Some(Box::new(Gauge::new(
-                [
-                    GaugeHandle::from(move |_| force.get()),
-                    GaugeHandle::from(move |diff| Some(diff * 100.0_f32)).relative(),
-                ],
-                0.0..=sail_width,
-                (-FRAC_PI_3 * 2.0)..=(FRAC_PI_3 * 2.0),
-            ))),
-            attachements,
-        },
-    );
-    ship.grid.insert(
-        (0, -1).into(),
-        Segment {
-            content: Some(Box::new(Gauge::new(
-                [
-                    GaugeHandle::from(move |_| Some(-current_angle.get()?)),
-                    GaugeHandle::from(move |diff| Some(diff * 1000.0_f32)).relative(),
-                    GaugeHandle::from(move |_| {
-                        Some((right_rope.get()? - left_rope.get()?) / 10.0 + PI)
-                    }),
-                ],
-                -FRAC_PI_2..=FRAC_PI_2,
-                -FRAC_PI_2..=FRAC_PI_2,
-            ))),
-            attachements: Default::default(),
-        },
-    );
-
-    let mut window = GameWindow::Ship;
-    let mut delete_save = start_coroutine(async {});
-    let do_delete = Arc::new(Mutex::new(false));
-    save::transaction_loop(|| {
-        if delete_save.is_done() {
-            if is_key_down(KeyCode::Delete) {
-                let do_delete = do_delete.clone();
-                delete_save = start_coroutine(async move {
-                    let timer = start_coroutine(async {
-                        wait_seconds(2.0).await;
-                    });
-                    while is_key_down(KeyCode::Delete) {
-                        if timer.is_done() {
-                            *do_delete.lock().unwrap() = true;
-                            return;
+            // Maneuver planning for the object selected in the map.
+            if is_key_pressed(KeyCode::N) {
+                orbits.plan_maneuver();
+                save::checkpoint();
+            }
+            if is_key_pressed(KeyCode::Equal) {
+                orbits.adjust_maneuver(1.0);
+                save::checkpoint();
+            }
+            if is_key_pressed(KeyCode::Minus) {
+                orbits.adjust_maneuver(-1.0);
+                save::checkpoint();
+            }
+            if is_key_pressed(KeyCode::X) {
+                orbits.remove_maneuver();
+                save::checkpoint();
+            }
+
+            if is_key_pressed(KeyCode::C) {
+                orbits.cycle_frame();
+            }
+
+            // Share saves, e.g. for bug reports.
+            if is_key_pressed(KeyCode::F5) {
+                miniquad::window::clipboard_set(&save::export());
+                notice = Some(("Save copied to clipboard".to_owned(), get_time()));
+            }
+            if is_key_pressed(KeyCode::F9) {
+                let text = miniquad::window::clipboard_get().unwrap_or_default();
+                // Never overwrite an earlier import.
+                let slot = save::slots::unused_name("imported");
+                let message = match save::import(&slot, &text) {
+                    Ok(missing) => {
+                        let message = if missing.is_empty() {
+                            format!("Save imported into slot `{slot}`")
+                        } else {
+                            format!(
+                                "Save imported into slot `{slot}`, using defaults for {}",
+                                missing.join(", ")
+                            )
+                        };
+                        menu = Some(Menu::Import { slot });
+                        message
+                    }
+                    Err(err) => format!("Import failed: {err}"),
+                };
+                notice = Some((message, get_time()));
+            }
+
+            // Going back to an older snapshot, e.g. after a bad maneuver.
+            if is_key_pressed(KeyCode::H) {
+                menu = match menu {
+                    Some(Menu::History { .. }) => None,
+                    _ => {
+                        let snapshots = save::history::list().into_iter().rev().map(|snapshot| {
+                            let minutes = (snapshot.play_time.unwrap_or(0.0) / 60.0) as u64;
+                            let text = format!(
+                                "#{} {} after {}h {:02}min of play",
+                                snapshot.id,
+                                snapshot.kind,
+                                minutes / 60,
+                                minutes % 60
+                            );
+                            (snapshot.id, text)
+                        });
+                        Some(Menu::History {
+                            snapshots: snapshots.collect(),
+                            selected: 0,
+                        })
+                    }
+                };
+            }
+            if is_key_pressed(KeyCode::Delete) {
+                menu = Some(Menu::NewGame);
+            }
+            match &mut menu {
+                Some(Menu::History {
+                    snapshots,
+                    selected,
+                }) => {
+                    if is_key_pressed(KeyCode::Down) && *selected + 1 < snapshots.len() {
+                        *selected += 1;
+                    }
+                    if is_key_pressed(KeyCode::Up) {
+                        *selected = selected.saturating_sub(1);
+                    }
+                    if is_key_pressed(KeyCode::Enter) {
+                        if let Some(&(id, _)) = snapshots.get(*selected) {
+                            restart = Some(Restart::Rollback(id));
+                            save::reload();
                         }
-                        next_frame().await
                     }
-                })
+                }
+                Some(Menu::NewGame) => {
+                    if is_key_pressed(KeyCode::Enter) {
+                        restart = Some(Restart::NewGame);
+                        save::reload();
+                    }
+                    if is_key_pressed(KeyCode::Escape) {
+                        menu = None;
+                    }
+                }
+                Some(Menu::Import { slot }) => {
+                    if is_key_pressed(KeyCode::Enter) {
+                        restart = Some(Restart::Select(std::mem::take(slot)));
+                        save::reload();
+                    }
+                    if is_key_pressed(KeyCode::Escape) {
+                        menu = None;
+                    }
+                }
+                None => {}
             }
-            if *do_delete.lock().unwrap() {
-                orbits.t.set(-1000.0);
-                *do_delete.lock().unwrap() = false;
+            if restart.is_some() {
+                menu = None;
+                let message = match restart {
+                    Some(Restart::NewGame) => "Starting a new game...",
+                    Some(Restart::Select(_)) => "Switching slots...",
+                    _ => "Rolling back...",
+                };
+                notice = Some((message.to_owned(), get_time()));
             }
-        }
-        // Logic
-        if !(cfg!(debug_assertions) && is_key_down(KeyCode::Space)) {
-            stars.update();
-            ship.update();
-            orbits.update();
-            player.update(&mut ship.grid);
-        }
 
-        if is_key_pressed(KeyCode::M) {
-            window = match window {
-                GameWindow::Ship => GameWindow::Orbit,
-                GameWindow::Orbit => GameWindow::Ship,
-            };
+            if is_key_pressed(KeyCode::M) {
+                window = match window {
+                    GameWindow::Ship => GameWindow::Orbit,
+                    GameWindow::Orbit => GameWindow::Ship,
+                };
+            }
+
+            let mut cam = Camera2D::default();
+            cam.zoom /= orbits::MAP_RADIUS;
+            cam.render_target = Some(orbit_render_target.clone());
+            set_camera(&cam);
+            clear_background(Color::default());
+            orbits.draw();
+
+            // HACK: remove after https://github.com/not-fl3/macroquad/pull/824 makes it into a release
+            set_default_camera();
+
+            let mut cam = Camera2D::default();
+            cam.zoom.x = 1.0 / (screen_width() / 2.0);
+            cam.zoom.y = 1.0 / (screen_height() / 2.0);
+            set_camera(&cam);
+
+            if is_mouse_button_pressed(MouseButton::Left) {
+                let mouse = cam.screen_to_world(mouse_position().into());
+                let mut areas = parts.map_areas.iter().filter_map(Reader::get);
+                if let Some(area) = areas.find(|area| area.contains(mouse)) {
+                    // The render target spans the map radius in every direction.
+                    let pos = (mouse - area.point()) / area.size() * 2.0 - Vec2::ONE;
+                    orbits.select_at(pos * orbits::MAP_RADIUS);
+                }
+            }
+
+            // Drawing
+            clear_background(BLACK);
+
+            stars.draw();
+            ship.draw();
+            player.draw();
+
+            let pos = cam.screen_to_world(vec2(0.0, 0.0));
+            draw_text(
+                "A: left, D: right, W/S control element at location",
+                pos.x + 20.0,
+                pos.y + 20.0,
+                30.0,
+                DARKGRAY,
+            );
+            if let Some((message, shown)) = &notice {
+                if get_time() - shown < 5.0 {
+                    draw_text(message, pos.x + 20.0, pos.y + 50.0, 30.0, YELLOW);
+                }
+            }
+            // Stays until saving works again, progress is lost if the game is closed in the meantime.
+            if let Some(err) = save::error() {
+                let message = format!("Saving failed: {err}");
+                draw_text(&message, pos.x + 20.0, pos.y + 80.0, 30.0, RED);
+            }
+            match &menu {
+                Some(Menu::History {
+                    snapshots,
+                    selected,
+                }) => {
+                    let help = if snapshots.is_empty() {
+                        "No snapshots yet, H: close"
+                    } else {
+                        "Up/Down: select, Enter: roll back, H: close"
+                    };
+                    draw_text(help, pos.x + 20.0, pos.y + 110.0, 30.0, DARKGRAY);
+                    for (i, (_, text)) in snapshots.iter().enumerate() {
+                        let color = if i == *selected { YELLOW } else { LIGHTGRAY };
+                        let y = pos.y + 140.0 + i as f32 * 30.0;
+                        draw_text(text, pos.x + 20.0, y, 30.0, color);
+                    }
+                }
+                Some(Menu::NewGame) => {
+                    draw_text(
+                        "Start a new game? The current one stays in the history (H).",
+                        pos.x + 20.0,
+                        pos.y + 110.0,
+                        30.0,
+                        YELLOW,
+                    );
+                    draw_text(
+                        "Enter: start over, Escape: keep playing",
+                        pos.x + 20.0,
+                        pos.y + 140.0,
+                        30.0,
+                        DARKGRAY,
+                    );
+                }
+                Some(Menu::Import { slot }) => {
+                    let question = format!(
+                        "Play the imported save? The current one stays in slot `{}`.",
+                        save::slots::active()
+                    );
+                    draw_text(&question, pos.x + 20.0, pos.y + 110.0, 30.0, YELLOW);
+                    let help = format!(
+                        "Enter: switch, Escape: keep playing (SOLAR_SAILORS_SLOT={slot} opens it later)"
+                    );
+                    draw_text(&help, pos.x + 20.0, pos.y + 140.0, 30.0, DARKGRAY);
+                }
+                None => {}
+            }
+
+            // Let the engine actually do stuff
+
+            next_frame()
+        })
+        .await;
+        if exit == save::Exit::Quit {
+            break;
         }
+        let result = match restart {
+            Some(Restart::Rollback(id)) => save::history::rollback(id)
+                .map(|()| format!("Rolled back to snapshot #{id}"))
+                .map_err(|err| format!("Rollback failed: {err}")),
+            Some(Restart::NewGame) => save::slots::reset()
+                .map(|()| "Started a new game".to_owned())
+                .map_err(|err| format!("Starting a new game failed: {err}")),
+            Some(Restart::Select(slot)) => save::slots::select(&slot)
+                .map(|()| format!("Playing slot `{slot}`"))
+                .map_err(|err| format!("Switching to slot `{slot}` failed: {err}")),
+            None => continue,
+        };
+        notice = Some((result.unwrap_or_else(|err| err), get_time()));
+    }
+}
+
+/// What the player is asked about.
+enum Menu {
+    /// Id and description of the snapshots to roll back to, newest first, and the selected one.
+    History {
+        snapshots: Vec<(usize, String)>,
+        selected: usize,
+    },
+    NewGame,
+    /// Whether to switch to the slot a save was just imported into.
+    Import {
+        slot: String,
+    },
+}
 
-        let mut cam = Camera2D::default();
-        cam.zoom /= 300.0;
-        cam.render_target = Some(orbit_render_target.clone());
-        set_camera(&cam);
-        clear_background(Color::default());
-        orbits.draw();
-
-        // HACK: remove after https://github.com/not-fl3/macroquad/pull/824 makes it into a release
-        set_default_camera();
-
-        let mut cam = Camera2D::default();
-        cam.zoom.x = 1.0 / (screen_width() / 2.0);
-        cam.zoom.y = 1.0 / (screen_height() / 2.0);
-        set_camera(&cam);
-
-        // Drawing
-        clear_background(BLACK);
-
-        stars.draw();
-        ship.draw();
-        player.draw();
-
-        let pos = cam.screen_to_world(vec2(0.0, 0.0));
-        draw_text(
-            "A: left, D: right, W/S control element at location",
-            pos.x + 20.0,
-            pos.y + 20.0,
-            30.0,
-            DARKGRAY,
-        );
-
-        // Let the engine actually do stuff
-
-        next_frame()
-    })
-    .await;
+/// What to do with the save game before loading everything again.
+enum Restart {
+    Rollback(usize),
+    NewGame,
+    /// Play in another slot.
+    Select(String),
 }
 
 enum GameWindow {
@@ -1,41 +1,261 @@
 pub use ::orbits::*;
+use ::orbits::{
+    frame::{Frame, Transform},
+    maneuver::Maneuver,
+    shadow::Light,
+};
 use macroquad::prelude::*;
 
-use crate::save::Saveable;
+use crate::{
+    datastructures::{Reader, Sensor, SetGet},
+    save::{self, ComplexSave, ComplexSaveable, Save, Saveable},
+};
 
 pub struct Orbits {
-    pub orbits: orbits::Orbits,
-    pub t: Saveable<f64>,
+    pub orbits: Saveable<Objects>,
+    pub t: Saveable<Time>,
+    /// Object clicked on in the map.
+    pub selected: Option<usize>,
+    /// The object the player's ship is.
+    pub ship: ComplexSaveable<Option<ObjectId>>,
+    /// Point of view of the map.
+    pub frame: Frame,
+    /// How much of the sun's light reaches the ship, see [Light::illumination].
+    sunlight: Sensor<f32>,
 }
 
-pub struct ObjectId(#[expect(dead_code)] usize);
+#[derive(Clone, Copy, Default, Save)]
+pub struct ObjectId(usize);
+
+/// How a planned [Maneuver] is saved.
+#[derive(Default, Save)]
+struct SavedManeuver {
+    t: Time,
+    dx: f64,
+    dy: f64,
+}
+
+/// All objects in orbit, saved as the number of objects at `<key>/len` and each object's id,
+/// orbit and planned maneuvers at `<key>/0/id`, `<key>/0/object`, `<key>/0/maneuvers`, ...
+#[derive(Default)]
+pub struct Objects(orbits::Orbits);
+
+impl From<orbits::Orbits> for Objects {
+    fn from(orbits: orbits::Orbits) -> Self {
+        Self(orbits)
+    }
+}
+
+impl std::ops::Deref for Objects {
+    type Target = orbits::Orbits;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Objects {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Save for Objects {
+    fn save(&self, key: impl std::fmt::Display) {
+        save::remove(&key);
+        self.iter().count().save(format_args!("{key}/len"));
+        for (i, (id, object)) in self.iter().enumerate() {
+            id.save(format_args!("{key}/{i}/id"));
+            object.save(format_args!("{key}/{i}/object"));
+            if let Some(plan) = self.plan(id) {
+                let maneuvers: Vec<_> = plan
+                    .maneuvers()
+                    .iter()
+                    .map(|maneuver| SavedManeuver {
+                        t: maneuver.t,
+                        dx: maneuver.delta_v.0,
+                        dy: maneuver.delta_v.1,
+                    })
+                    .collect();
+                ComplexSave::from(maneuvers).save(format_args!("{key}/{i}/maneuvers"));
+            }
+        }
+    }
+
+    fn load(&mut self, key: impl std::fmt::Display) {
+        let Some(len) = save::load::<usize>(format_args!("{key}/len")) else {
+            return;
+        };
+        let mut orbits = orbits::Orbits::default();
+        for i in 0..len {
+            let id = save::load(format_args!("{key}/{i}/id"));
+            let object = save::load(format_args!("{key}/{i}/object"));
+            let (Some(id), Some(object)) = (id, object) else {
+                warn!("dropping saved object {i}, it is corrupt");
+                continue;
+            };
+            orbits.insert_with_id(id, object);
+            let mut maneuvers = ComplexSave::<Vec<SavedManeuver>>::default();
+            maneuvers.load(format_args!("{key}/{i}/maneuvers"));
+            if !maneuvers.is_empty() {
+                let plan = orbits.plan_mut(id).unwrap();
+                for maneuver in maneuvers.iter() {
+                    plan.insert(Maneuver {
+                        t: maneuver.t,
+                        delta_v: (maneuver.dx, maneuver.dy),
+                    });
+                }
+            }
+        }
+        self.0 = orbits;
+    }
+}
 
 const MOON_SIZE: f32 = 20.0;
+/// Distance from the center of the map to its edges.
+pub const MAP_RADIUS: f32 = 300.0;
+/// How far away from an object a click may be to still select it.
+const SELECT_RADIUS: f64 = 15.0;
+/// Half the angle the sun covers in the sky. Much larger than in reality, so the penumbra is visible.
+const SUN_ANGULAR_RADIUS: f64 = 0.05;
+/// How far ahead of now new maneuver nodes are planned.
+const NODE_LEAD: f64 = 1000.0;
+/// Change of velocity in the direction of travel per key press when editing a maneuver node.
+const NODE_STEP: f64 = 0.002;
+/// Colors of the predicted trajectory after the first, second, ... maneuver.
+const PLAN_COLORS: [Color; 4] = [SKYBLUE, ORANGE, PINK, VIOLET];
+
+/// The sun is infinitely far away in positive y direction.
+fn sun() -> Light {
+    Light::new((0.0, 1.0), SUN_ANGULAR_RADIUS)
+}
 
 impl Orbits {
-    pub fn load() -> Self {
+    /// Load the saved objects, or start with the objects of a scene authored with the
+    /// `solar_playground` example if nothing was saved yet.
+    pub fn load(scene: &str) -> Self {
+        let scene: orbits::Orbits = scene.parse().unwrap();
+        // The ship is the first object of the scene.
+        let ship = scene.iter().next().map(|(id, _)| ObjectId(id));
         Self {
-            orbits: Default::default(),
+            orbits: Saveable::new(scene, "orbits/objects"),
             t: Saveable::default("time"),
+            selected: None,
+            ship: Saveable::new(ship, "orbits/ship"),
+            frame: Frame::INERTIAL,
+            sunlight: Sensor::raw(1.0),
         }
     }
-    pub fn insert(&mut self, object: orbits::Object) -> ObjectId {
-        ObjectId(self.orbits.insert(object))
+    pub fn sunlight(&self) -> Reader<f32> {
+        self.sunlight.make_reader()
+    }
+    fn ship(&self) -> Option<usize> {
+        self.ship.map(|ObjectId(id)| id)
     }
     pub fn update(&mut self) {
-        self.t += 10.0;
+        self.t += Time::from(10.0);
+        // Rebasing doesn't move anything, so only save the objects when it is actually needed.
+        if self.orbits.iter().any(|(_, &object)| {
+            let mut object = object;
+            object.rebase()
+        }) {
+            self.orbits.update(|orbits| orbits.rebase());
+        }
         // only need to do something for objects under thrust
+
+        if let Some(ship) = self.ship().and_then(|id| self.orbits.get(id)) {
+            let pos = ship.position_at(*self.t);
+            self.sunlight
+                .set(sun().illumination(MOON_SIZE.into(), pos) as f32);
+        }
+    }
+    /// Switch the map between centering on the moon, on the ship, and on the ship with the moon always below it.
+    pub fn cycle_frame(&mut self) {
+        let Some(ship) = self.ship() else {
+            return;
+        };
+        self.frame = if self.frame == Frame::INERTIAL {
+            Frame::object(ship)
+        } else if self.frame == Frame::object(ship) {
+            Frame::co_rotating(ship)
+        } else {
+            Frame::INERTIAL
+        };
+    }
+    /// The transform from the coordinates of the moon into the map's frame.
+    fn transform(&self) -> Transform {
+        self.orbits
+            .transform(self.frame, *self.t)
+            .unwrap_or(Transform::IDENTITY)
+    }
+    /// Select the object closest to `pos` (in map coordinates), or deselect if there is none nearby.
+    pub fn select_at(&mut self, pos: Vec2) {
+        let index = self.orbits.spatial_index(*self.t, SELECT_RADIUS);
+        let pos = self.transform().inverse((pos.x.into(), pos.y.into()));
+        self.selected = index
+            .nearest(pos)
+            .filter(|&(_, distance)| distance <= SELECT_RADIUS)
+            .map(|(id, _)| id);
+    }
+
+    /// Plan a new maneuver for the selected object a bit into the future.
+    pub fn plan_maneuver(&mut self) {
+        let t = *self.t + NODE_LEAD;
+        let Some(id) = self.selected else {
+            return;
+        };
+        self.orbits.update(|orbits| {
+            if let Some(plan) = orbits.plan_mut(id) {
+                plan.insert(Maneuver {
+                    t,
+                    delta_v: (0.0, 0.0),
+                });
+            }
+        });
+    }
+    /// Change the velocity of the last planned maneuver of the selected object
+    /// by `steps` in the direction of travel.
+    pub fn adjust_maneuver(&mut self, steps: f64) {
+        let Some(id) = self.selected else {
+            return;
+        };
+        self.orbits.update(|orbits| {
+            let Some(plan) = orbits.plan_mut(id) else {
+                return;
+            };
+            let Some(idx) = plan.maneuvers().len().checked_sub(1) else {
+                return;
+            };
+            let mut maneuver = plan.maneuvers()[idx];
+            let (x, y) = plan.segment_at(maneuver.t).object.direction_at(maneuver.t);
+            maneuver.delta_v.0 += x * NODE_STEP * steps;
+            maneuver.delta_v.1 += y * NODE_STEP * steps;
+            plan.edit(idx, maneuver);
+        });
+    }
+    /// Forget the last planned maneuver of the selected object.
+    pub fn remove_maneuver(&mut self) {
+        let Some(id) = self.selected else {
+            return;
+        };
+        self.orbits.update(|orbits| {
+            if let Some(plan) = orbits.plan_mut(id) {
+                if let Some(idx) = plan.maneuvers().len().checked_sub(1) {
+                    plan.remove(idx);
+                }
+            }
+        });
     }
-    pub fn draw(&self) {
-        for (kind, pos, mut points) in self.orbits.draw(*self.t, 100) {
-            let pos = Vec2::from(pos);
-            let size = 10.0;
-            let y = f32::sin(std::f32::consts::PI / 3.0) * size;
-            let x = size / 2.0;
-            let left = Vec2::new(-x, y);
-            let right = Vec2::new(x, y);
-            draw_triangle(pos, pos + left, pos + right, GREEN);
 
+    pub fn draw(&self) {
+        let transform = self.transform();
+        let at = |pos| vec(transform.apply(pos));
+        let dir = |dir| vec(transform.rotate(dir));
+        let tessellation = Tessellation::Tolerance {
+            max_error: 0.25,
+            max_radius: 500.0,
+        };
+        for (kind, _pos, points) in self.orbits.draw(*self.t, tessellation) {
             let color = match kind {
                 OrbitKind::Circle => WHITE,
                 OrbitKind::Ellipse => GRAY,
@@ -43,21 +263,132 @@ impl Orbits {
                 OrbitKind::Hyperbola => RED,
             };
 
-            let (mut x, mut y) = points.next().unwrap();
+            let mut points = points.map(|(x, y)| at((x.into(), y.into())));
+            let mut prev = points.next().unwrap();
+            for next in points {
+                draw_line(prev.x, prev.y, next.x, next.y, 0.5, color);
+                prev = next;
+            }
+        }
+        self.draw_plans(&transform);
+        for (_, object) in self.orbits.iter() {
+            let pe = at(object.periapsis());
+            draw_circle(pe.x, pe.y, 2.0, SKYBLUE);
+            if let Some(ap) = object.apoapsis() {
+                let ap = at(ap);
+                draw_circle(ap.x, ap.y, 2.0, ORANGE);
+            }
+            if let Some(focus) = object.second_focus() {
+                let focus = at(focus);
+                draw_circle_lines(focus.x, focus.y, 2.0, 0.5, DARKGRAY);
+            }
+            if let (Some(center), Some(asymptotes)) = (object.center(), object.asymptotes()) {
+                let center = at(center);
+                for &asymptote in asymptotes.iter() {
+                    let end = center + dir(asymptote) * 1000.0;
+                    draw_line(center.x, center.y, end.x, end.y, 0.25, DARKGRAY);
+                }
+            }
 
-            for (new_x, new_y) in points {
-                draw_line(x, y, new_x, new_y, 0.5, color);
-                x = new_x;
-                y = new_y;
+            // Arrow pointing in the direction of travel, with its tip on the object.
+            let pos = at(object.position_at(*self.t));
+            let dir = dir(object.direction_at(*self.t));
+            let size = 10.0;
+            let back = -dir * f32::sin(std::f32::consts::PI / 3.0) * size;
+            let side = dir.perp() * size / 2.0;
+            draw_triangle(pos, pos + back + side, pos + back - side, GREEN);
+        }
+        if let Some(object) = self.selected.and_then(|id| self.orbits.get(id)) {
+            let pos = at(object.position_at(*self.t));
+            draw_circle_lines(pos.x, pos.y, SELECT_RADIUS as f32, 1.0, YELLOW);
+        }
+        let moon = at((0.0, 0.0));
+        draw_circle(moon.x, moon.y, MOON_SIZE, GRAY);
+        self.draw_shadow(&transform);
+    }
+
+    /// Draw the predicted trajectory after each planned maneuver in its own color.
+    fn draw_plans(&self, transform: &Transform) {
+        for (id, _) in self.orbits.iter() {
+            let Some(plan) = self.orbits.plan(id) else {
+                continue;
+            };
+            let arcs: Vec<_> = plan.arcs(*self.t, 0.25, 500.0).collect();
+            // Segments that already ended are skipped by `arcs`.
+            let skipped = plan.segments().len() - arcs.len();
+            for (idx, arc) in arcs.iter().enumerate() {
+                // The segment before any maneuvers is the current orbit, which is drawn already.
+                let Some(maneuvers) = (skipped + idx).checked_sub(1) else {
+                    continue;
+                };
+                let color = PLAN_COLORS[maneuvers % PLAN_COLORS.len()];
+                for pair in arc.windows(2) {
+                    let (a, b) = (transform.apply(pair[0]), transform.apply(pair[1]));
+                    let (a, b) = (vec(a), vec(b));
+                    draw_line(a.x, a.y, b.x, b.y, 0.5, color);
+                }
+            }
+            for (idx, maneuver) in plan.maneuvers().iter().enumerate() {
+                let pos = vec(transform.apply(plan.position_at(maneuver.t)));
+                let color = PLAN_COLORS[idx % PLAN_COLORS.len()];
+                draw_circle_lines(pos.x, pos.y, 3.0, 1.0, color);
             }
         }
-        draw_circle(0.0, 0.0, MOON_SIZE, GRAY);
-        draw_rectangle(
-            -MOON_SIZE,
-            0.0,
-            MOON_SIZE * 2.0,
-            -1000.0,
+    }
+
+    fn draw_shadow(&self, transform: &Transform) {
+        let sun = sun();
+        let radius = MOON_SIZE.into();
+        let moon = vec(transform.apply((0.0, 0.0)));
+        let axis = -vec(transform.rotate(sun.direction()));
+        let side = axis.perp();
+        let at = |distance: f64, offset: f64| moon + axis * distance as f32 + side * offset as f32;
+        // Far enough to leave the map in every direction.
+        let far = 1000.0;
+        let start = sun.umbra_radius(radius, 0.0);
+        let end = sun.penumbra_radius(radius, far);
+        let penumbra = Color::new(0.0, 0.0, 0.0, 0.25);
+        draw_triangle(at(0.0, -start), at(far, -end), at(far, end), penumbra);
+        draw_triangle(at(0.0, -start), at(0.0, start), at(far, end), penumbra);
+        // The umbra ends where its radius reaches zero.
+        let tip = start / SUN_ANGULAR_RADIUS.tan();
+        draw_triangle(
+            at(0.0, -start),
+            at(0.0, start),
+            at(tip, 0.0),
             Color::new(0.0, 0.0, 0.0, 0.5),
         );
     }
 }
+
+fn vec((x, y): (f64, f64)) -> Vec2 {
+    vec2(x as f32, y as f32)
+}
+
+#[test]
+fn objects_and_plans_survive_a_restart() {
+    use crate::save::{self, backend::Memory, storage::Transaction};
+
+    save::set_backend(Memory::default());
+    let scene = include_str!("../assets/start.orbits");
+    let mut orbits = Orbits::load(scene);
+    {
+        let mut transaction = Transaction::start();
+        orbits.orbits.update(|orbits| {
+            orbits.remove(1);
+            let debris = "angle=1 t=5+0.5 p=150 epsilon=0.5".parse().unwrap();
+            assert_eq!(orbits.insert(debris), 2);
+        });
+        orbits.selected = Some(2);
+        orbits.plan_maneuver();
+        orbits.adjust_maneuver(3.0);
+        transaction.commit().unwrap();
+    }
+    let loaded = Orbits::load(scene);
+    assert_eq!(loaded.orbits.to_string(), orbits.orbits.to_string());
+    let ids: Vec<_> = loaded.orbits.iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, [0, 2]);
+    let plan = loaded.orbits.plan(2).unwrap();
+    assert_eq!(plan.maneuvers(), orbits.orbits.plan(2).unwrap().maneuvers());
+    assert_eq!(loaded.ship(), Some(0));
+}
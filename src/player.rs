@@ -8,7 +8,7 @@ use macroquad::prelude::{
 
 use crate::{
     datastructures::{Sensor, SetGet},
-    save::{ComplexSaveable, Saveable},
+    save::{ComplexSaveable, Save, Saveable},
     ship::{Attachement, Segment, ATTACHEMENT_ANGLES, ATTACHEMENT_OFFSETS, SIZE, SPACING, SQRT3},
 };
 
@@ -22,16 +22,22 @@ pub struct Player {
     texture: Texture2D,
     anim: AnimatedSprite,
     animations: [Animation; 4],
-    action: Action,
+    /// Saved, so a sleeping crab is still asleep after a restart.
+    action: Saveable<Action>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Save)]
 enum Action {
+    #[default]
     Idle,
-    Walk { right: bool },
+    Walk {
+        right: bool,
+    },
     Sleep,
     Wake,
-    Use { up: bool },
+    Use {
+        up: bool,
+    },
 }
 
 impl Player {
@@ -80,7 +86,7 @@ impl Player {
             x: Saveable::default("player/side_pos"),
             anim,
             animations,
-            action: Action::Idle,
+            action: Saveable::default("player/action"),
         }
     }
 
@@ -131,12 +137,12 @@ impl Player {
                 (false, false) => None,
             },
         };
-        match self.action {
+        match *self.action {
             // Wake up whenever in the final sleeping frames
             Action::Sleep if self.i > 1 => match next_action {
                 None | Some(Action::Sleep) => {}
                 Some(_) => {
-                    self.action = Action::Wake;
+                    self.action.set(Action::Wake);
                     self.i = 0;
                     self.speed = 0;
                 }
@@ -144,7 +150,7 @@ impl Player {
             // The idle and walk action can immediately be overwritten
             Action::Walk { .. } | Action::Idle | Action::Use { .. } => {
                 let next_action = next_action.unwrap_or(Action::Idle);
-                if next_action == self.action {
+                if next_action == *self.action {
                     let dir = match next_action {
                         Action::Use { up } => Some(up),
                         _ => None,
@@ -154,7 +160,7 @@ impl Player {
                         attachement.control(dir, Some(self.x()));
                     }
                 } else {
-                    self.action = next_action;
+                    self.action.set(next_action);
                     self.i = 0;
                     self.speed = 0;
                 }
@@ -164,7 +170,7 @@ impl Player {
 
         self.speed += 1;
         // Only step the animation every few frames.
-        let speed_limit = match self.action {
+        let speed_limit = match *self.action {
             Action::Walk { .. } => 3,
             Action::Sleep => 30,
             Action::Wake => 10,
@@ -174,7 +180,7 @@ impl Player {
         if self.speed == speed_limit {
             self.speed = 0;
             self.i += 1;
-            if let Action::Walk { right } = self.action {
+            if let Action::Walk { right } = *self.action {
                 if right {
                     self.x += 2;
                 } else {
@@ -216,17 +222,17 @@ impl Player {
                     }
                 }
             }
-            let action_id = match self.action {
+            let action_id = match *self.action {
                 Action::Idle => 0,
                 Action::Walk { .. } => 1,
                 Action::Wake | Action::Sleep => 2,
                 Action::Use { .. } => 3,
             };
             if self.i == self.animations[action_id].frames {
-                match self.action {
+                match *self.action {
                     Action::Sleep => self.i -= 2,
                     Action::Use { .. } | Action::Wake | Action::Walk { .. } | Action::Idle => {
-                        self.action = next_action.unwrap_or(Action::Idle);
+                        self.action.set(next_action.unwrap_or(Action::Idle));
                         self.i = 0;
                     }
                 }
@@ -234,7 +240,7 @@ impl Player {
             self.anim.set_animation(action_id);
         }
 
-        self.anim.set_frame(match self.action {
+        self.anim.set_frame(match *self.action {
             Action::Idle | Action::Walk { .. } | Action::Sleep => self.i,
             Action::Wake => 3 - self.i,
             Action::Use { up: true } => self.i,
@@ -262,7 +268,7 @@ impl Player {
         const BASE_SCALE: f32 = (SIZE / 2.0 + ANIM_OFFSET as f32) / (SIZE / 2.0);
         let pos = center + base * BASE_SCALE + offset;
 
-        let flip_x = match self.action {
+        let flip_x = match *self.action {
             Action::Idle => false,
             Action::Walk { right } => right,
             Action::Wake | Action::Sleep => false,
@@ -1,30 +1,59 @@
 use std::{
     cmp::Ordering,
+    collections::HashMap,
     fmt::{Debug, Display},
+    hash::Hash,
     ops::{AddAssign, Deref, DerefMut, DivAssign, MulAssign, RemAssign, SubAssign},
     str::FromStr,
 };
 
 use hex2d::Coordinate;
-
-mod storage;
-pub use storage::transaction_loop;
+use macroquad::logging::warn;
+
+pub mod backend;
+mod export;
+pub mod history;
+pub mod inspect;
+pub mod migrate;
+pub mod schema;
+pub mod slots;
+pub mod storage;
+pub use export::{export, import};
+pub use save_derive::Save;
+pub use storage::{
+    checkpoint, error, reload, set_autosave_interval, set_backend, transaction_loop, Exit,
+};
 
 use crate::datastructures::SetGet;
 
-fn save(key: impl ToString, value: impl ToString) {
+/// Save a single value, e.g. the variant of an enum.
+pub fn save(key: impl ToString, value: impl ToString) {
     storage::set(&key.to_string(), &value.to_string())
 }
 
-fn load<T: FromStr>(key: impl ToString) -> Option<T>
+/// Remove a key and everything below it, e.g. the elements of a collection before saving it again,
+/// so no elements are left behind when it shrinks.
+pub fn remove(key: impl ToString) {
+    storage::remove(&key.to_string())
+}
+
+/// Load a single value. Values that don't parse, e.g. after editing the save by hand, are replaced
+/// by the value of the frame before. If that doesn't parse either, `None` is returned.
+pub fn load<T: FromStr>(key: impl ToString) -> Option<T>
 where
     T::Err: Debug,
 {
-    try_load(key).map(Result::unwrap)
-}
-
-fn try_load<T: FromStr>(key: impl ToString) -> Option<Result<T, T::Err>> {
-    storage::get(&key.to_string()).map(|s| s.parse())
+    let key = key.to_string();
+    schema::check::<T>(&key);
+    let parse = |value: String| match value.parse() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("save key `{key}` has invalid value {value:?}: {err:?}");
+            None
+        }
+    };
+    // A missing value is not corrupt, it may have been removed on purpose by a migration.
+    parse(storage::get(&key)?).or_else(|| parse(storage::get_previous(&key)?))
 }
 
 pub trait Save {
@@ -45,6 +74,7 @@ impl<T: Save> Saveable<T> {
             value: value.into(),
             key: key.to_string(),
         };
+        storage::register(&this.key);
         this.load();
         this
     }
@@ -136,7 +166,7 @@ where
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
 pub struct ComplexSave<T>(T);
 
 impl<T> From<T> for ComplexSave<T> {
@@ -170,6 +200,113 @@ impl Save for ComplexSave<Coordinate> {
     }
 }
 
+/// The number of elements at `<key>/len` and the elements at `<key>/0`, `<key>/1`, ...
+impl<T: Save + Default> Save for ComplexSave<Vec<T>> {
+    fn save(&self, key: impl Display) {
+        remove(&key);
+        self.len().save(format_args!("{key}/len"));
+        for (i, elem) in self.iter().enumerate() {
+            elem.save(format_args!("{key}/{i}"));
+        }
+    }
+
+    fn load(&mut self, key: impl Display) {
+        if let Some(len) = load(format_args!("{key}/len")) {
+            let len = stored_len(&key.to_string(), len);
+            self.resize_with(len, T::default);
+            for (i, elem) in self.iter_mut().enumerate() {
+                elem.load(format_args!("{key}/{i}"));
+            }
+        }
+    }
+}
+
+/// The elements at `<key>/0`, `<key>/1`, ...
+impl<T: Save, const N: usize> Save for ComplexSave<[T; N]> {
+    fn save(&self, key: impl Display) {
+        for (i, elem) in self.iter().enumerate() {
+            elem.save(format_args!("{key}/{i}"));
+        }
+    }
+
+    fn load(&mut self, key: impl Display) {
+        for (i, elem) in self.iter_mut().enumerate() {
+            elem.load(format_args!("{key}/{i}"));
+        }
+    }
+}
+
+/// Whether there is a value at `<key>/some` and the value itself at `<key>/value`.
+impl<T: Save + Default> Save for ComplexSave<Option<T>> {
+    fn save(&self, key: impl Display) {
+        remove(&key);
+        self.is_some().save(format_args!("{key}/some"));
+        if let Some(value) = &**self {
+            value.save(format_args!("{key}/value"));
+        }
+    }
+
+    fn load(&mut self, key: impl Display) {
+        match load(format_args!("{key}/some")) {
+            Some(true) => self
+                .get_or_insert_with(T::default)
+                .load(format_args!("{key}/value")),
+            Some(false) => **self = None,
+            None => {}
+        }
+    }
+}
+
+/// The number of entries at `<key>/len` and the entries at `<key>/0/key`, `<key>/0/value`, ...
+/// in no particular order.
+impl<K: Save + Default + Eq + Hash, V: Save + Default> Save for ComplexSave<HashMap<K, V>> {
+    fn save(&self, key: impl Display) {
+        remove(&key);
+        self.len().save(format_args!("{key}/len"));
+        for (i, (k, v)) in self.iter().enumerate() {
+            k.save(format_args!("{key}/{i}/key"));
+            v.save(format_args!("{key}/{i}/value"));
+        }
+    }
+
+    fn load(&mut self, key: impl Display) {
+        let Some(len) = load(format_args!("{key}/len")) else {
+            return;
+        };
+        let len = stored_len(&key.to_string(), len);
+        // Values that are still there keep everything that isn't saved.
+        let mut previous = std::mem::take(&mut **self);
+        for i in 0..len {
+            // Loading it as the default key would make several entries collapse into one.
+            if storage::keys(&format!("{key}/{i}/key")).is_empty() {
+                warn!("save key `{key}/{i}/key` is missing, skipping the entry");
+                continue;
+            }
+            let mut k = K::default();
+            k.load(format_args!("{key}/{i}/key"));
+            let mut v = previous.remove(&k).unwrap_or_default();
+            v.load(format_args!("{key}/{i}/value"));
+            self.insert(k, v);
+        }
+    }
+}
+
+/// The `len` of the collection at `key`, but no more than the elements that are actually stored,
+/// so a corrupt length doesn't allocate all the memory there is.
+fn stored_len(key: &str, len: usize) -> usize {
+    let stored = storage::keys(key)
+        .iter()
+        .filter_map(|stored| stored[key.len()..].split('/').nth(1)?.parse::<usize>().ok())
+        .map(|i| i + 1)
+        .max()
+        .unwrap_or(0);
+    if len > stored {
+        warn!("save key `{key}/len` is {len}, but there are only {stored} elements");
+        return stored;
+    }
+    len
+}
+
 impl<T: Save + Copy> SetGet for Saveable<T> {
     type Val = T;
 
@@ -181,3 +318,161 @@ impl<T: Save + Copy> SetGet for Saveable<T> {
         self.update(|v| *v = val);
     }
 }
+
+#[test]
+fn derive_nests_keys_by_field_name() {
+    #[derive(Save, Default, Debug, PartialEq)]
+    struct Gauge<T> {
+        value: T,
+        bounds: Bounds,
+        #[save(skip)]
+        dragged: bool,
+        #[save(skip, default = 1.0)]
+        blink: f32,
+        #[save(default = 3)]
+        handles: u8,
+    }
+
+    #[derive(Save, Default, Debug, PartialEq)]
+    struct Bounds(f32, f32);
+
+    #[derive(Save, Default, Debug, PartialEq)]
+    // `crate` would work just as well, this checks that the path is used.
+    #[save(crate = super)]
+    enum Content {
+        #[default]
+        Empty,
+        Gauge(#[save(default = 7)] u8, Gauge<i32>),
+        Sail {
+            width: f32,
+        },
+    }
+
+    storage::set_backend(backend::Memory::default());
+    let mut content = Saveable::<Content>::default("content");
+    {
+        let mut transaction = storage::Transaction::start();
+        content.update(|content| {
+            *content = Content::Gauge(
+                2,
+                Gauge {
+                    value: -4,
+                    bounds: Bounds(0.5, 1.5),
+                    dragged: true,
+                    blink: 0.0,
+                    handles: 0,
+                },
+            )
+        });
+        transaction.commit().unwrap();
+    }
+    assert_eq!(storage::get("content/variant").as_deref(), Some("Gauge"));
+    assert_eq!(storage::get("content/Gauge/1/value").as_deref(), Some("-4"));
+    assert_eq!(
+        storage::get("content/Gauge/1/bounds/1").as_deref(),
+        Some("1.5")
+    );
+    assert_eq!(storage::get("content/Gauge/1/dragged"), None);
+    assert_eq!(
+        *Saveable::<Content>::default("content"),
+        Content::Gauge(
+            2,
+            Gauge {
+                value: -4,
+                bounds: Bounds(0.5, 1.5),
+                dragged: false,
+                blink: 1.0,
+                handles: 0,
+            }
+        )
+    );
+
+    // Switching variants forgets everything of the other variants.
+    {
+        let mut transaction = storage::Transaction::start();
+        content.update(|content| *content = Content::Sail { width: 2.0 });
+        transaction.commit().unwrap();
+    }
+    assert_eq!(storage::get("content/Sail/width").as_deref(), Some("2"));
+    storage::with(|storage| {
+        let frame = format!(
+            "slots/default/{}/",
+            storage::read_odd(&*storage.backend, "default").unwrap() as u8
+        );
+        assert_eq!(
+            storage.backend.keys(&format!("{frame}content/Gauge")),
+            Vec::<String>::new()
+        );
+    });
+}
+
+#[test]
+fn collections_clean_up_stale_elements() {
+    storage::set_backend(backend::Memory::default());
+    let mut list = ComplexSaveable::<Vec<u8>>::default("list");
+    let mut map = ComplexSaveable::<HashMap<u8, ComplexSave<Option<f32>>>>::default("map");
+    let mut array = ComplexSaveable::<[i32; 2]>::default("array");
+    {
+        let mut transaction = storage::Transaction::start();
+        list.update(|list| list.extend([1, 2, 3]));
+        map.update(|map| {
+            map.insert(4, Some(0.5).into());
+            map.insert(5, None.into());
+        });
+        array.update(|array| array[1] = -1);
+        transaction.commit().unwrap();
+        list.update(|list| list.truncate(1));
+        map.update(|map| {
+            map.remove(&4);
+        });
+        transaction.commit().unwrap();
+        // The other buffer catches up with the removals, too.
+        transaction.commit().unwrap();
+        list.update(|list| list.push(6));
+        transaction.commit().unwrap();
+    }
+    assert_eq!(**ComplexSaveable::<Vec<u8>>::default("list"), [1, 6]);
+    let map = ComplexSaveable::<HashMap<u8, ComplexSave<Option<f32>>>>::default("map");
+    assert_eq!(**map, HashMap::from([(5, None.into())]));
+    assert_eq!(**ComplexSaveable::<[i32; 2]>::default("array"), [0, -1]);
+    // Nothing is left behind in either buffer.
+    storage::with(|storage| {
+        for odd in 0..2 {
+            let buffer = format!("slots/default/{odd}/");
+            for key in ["list/2", "map/1/key", "map/0/value/value"] {
+                assert_eq!(
+                    storage.backend.get(&format!("{buffer}{key}")),
+                    None,
+                    "{key}"
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn corrupt_collections_load_what_is_there() {
+    use backend::StorageBackend;
+
+    let mut backend = backend::Memory::default();
+    for (key, value) in [
+        ("odd", "false"),
+        ("0/list/len", "1000000000000000000"),
+        ("0/list/0", "1"),
+        ("0/list/2", "3"),
+        ("0/map/len", "3"),
+        ("0/map/0/key", "4"),
+        ("0/map/0/value", "5"),
+        ("0/map/1/value", "6"),
+        ("0/map/2/key", "7"),
+        ("0/map/2/value", "8"),
+    ] {
+        backend.set(&format!("slots/default/{key}"), value).unwrap();
+    }
+    storage::set_backend(backend);
+    assert_eq!(**ComplexSaveable::<Vec<u8>>::default("list"), [1, 0, 3]);
+    assert_eq!(
+        **ComplexSaveable::<HashMap<u8, u8>>::default("map"),
+        HashMap::from([(4, 5), (7, 8)])
+    );
+}
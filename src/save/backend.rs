@@ -0,0 +1,278 @@
+//! Places the raw key value pairs of a save game can be stored in.
+//!
+//! Keys are `/` separated paths like `0/player/side_pos`. Backends don't know anything about
+//! frames or transactions, that is all handled by the storage module on top of them.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::{collections::BTreeMap, io};
+
+pub trait StorageBackend {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()>;
+    /// Removing a key that doesn't exist is not an error.
+    fn remove(&mut self, key: &str) -> io::Result<()>;
+    /// All keys starting with `prefix`, in no particular order.
+    fn keys(&self, prefix: &str) -> Vec<String>;
+
+    /// Replace everything starting with `to` by a copy of everything starting with `from`.
+    fn copy_all(&mut self, from: &str, to: &str) -> io::Result<()> {
+        self.remove_all(to)?;
+        for key in self.keys(from) {
+            // Unreadable values are treated as missing, just like when loading them.
+            if let Some(value) = self.get(&key) {
+                self.set(&format!("{to}{}", &key[from.len()..]), &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all keys starting with `prefix`.
+    fn remove_all(&mut self, prefix: &str) -> io::Result<()> {
+        for key in self.keys(prefix) {
+            self.remove(&key)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend + ?Sized> StorageBackend for Box<B> {
+    fn get(&self, key: &str) -> Option<String> {
+        (**self).get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        (**self).set(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        (**self).remove(key)
+    }
+
+    fn keys(&self, prefix: &str) -> Vec<String> {
+        (**self).keys(prefix)
+    }
+}
+
+/// Fails to write anything to the backend it wraps.
+pub struct ReadOnly<B>(pub B);
+
+impl<B: StorageBackend> StorageBackend for ReadOnly<B> {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get(key)
+    }
+
+    fn set(&mut self, key: &str, _: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("can't write `{key}`, the storage is read only"),
+        ))
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("can't remove `{key}`, the storage is read only"),
+        ))
+    }
+
+    fn keys(&self, prefix: &str) -> Vec<String> {
+        self.0.keys(prefix)
+    }
+}
+
+/// The backend used if nothing else was chosen at startup.
+pub fn platform() -> Box<dyn StorageBackend> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Box::new(LocalStorage)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Box::new(FileSystem::project())
+    }
+}
+
+/// One file per key, with the key's path segments as directories.
+/// Keys can't end in [TMP_SUFFIX].
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileSystem {
+    root: PathBuf,
+}
+
+/// Appended to the file name of a key while its value gets written.
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) const TMP_SUFFIX: &str = ".tmp~";
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSystem {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The data directory of the game in the user's home directory.
+    pub fn project() -> Self {
+        Self::new(
+            directories::ProjectDirs::from("", "", "solar_sailors")
+                .map(|dirs| dirs.data_local_dir().to_owned())
+                .unwrap_or_default(),
+        )
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let mut path = self.root.clone();
+        for elem in key.split('/') {
+            path.push(elem);
+        }
+        path
+    }
+
+    fn collect_keys(dir: PathBuf, key: String, keys: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let key = if key.is_empty() {
+                name
+            } else {
+                format!("{key}/{name}")
+            };
+            if entry.path().is_dir() {
+                Self::collect_keys(entry.path(), key, keys);
+            } else if !key.ends_with(TMP_SUFFIX) {
+                keys.push(key);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBackend for FileSystem {
+    fn get(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.path(key)).ok()
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        if key.ends_with(TMP_SUFFIX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("key `{key}` ends in `{TMP_SUFFIX}`"),
+            ));
+        }
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        // Rename instead of writing in place, so a crash never leaves a half written value.
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(TMP_SUFFIX);
+        std::fs::write(&tmp, value)?;
+        std::fs::rename(tmp, path)
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        let path = self.path(key);
+        match std::fs::remove_file(&path) {
+            // A directory only holds the keys below `key`, `key` itself has no value.
+            Err(err) if err.kind() != io::ErrorKind::NotFound && !path.is_dir() => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self, prefix: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        // Only the directory the prefix ends in can contain matching keys.
+        match prefix.rsplit_once('/') {
+            Some((dir, _)) => Self::collect_keys(self.path(dir), dir.to_owned(), &mut keys),
+            None => Self::collect_keys(self.root.clone(), String::new(), &mut keys),
+        }
+        keys.retain(|key| key.starts_with(prefix));
+        keys
+    }
+}
+
+/// The browser's `localStorage`.
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl StorageBackend for LocalStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        quad_storage_sys::get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        quad_storage_sys::set(key, value);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        quad_storage_sys::remove(key);
+        Ok(())
+    }
+
+    fn keys(&self, prefix: &str) -> Vec<String> {
+        (0..quad_storage_sys::len())
+            .filter_map(quad_storage_sys::key)
+            .filter(|key| key.starts_with(prefix))
+            .collect()
+    }
+}
+
+/// Keeps everything in memory and forgets it when the game quits.
+#[derive(Default, Clone, Debug)]
+pub struct Memory {
+    values: BTreeMap<String, String>,
+}
+
+impl StorageBackend for Memory {
+    fn get(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.values.insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        self.values.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self, prefix: &str) -> Vec<String> {
+        self.values
+            .range(prefix.to_owned()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn file_system_keys() {
+    let root = std::env::temp_dir().join(format!("solar_sailors_keys_{}", std::process::id()));
+    let mut backend = FileSystem::new(&root);
+    for key in ["a/b/c", "a/bc", "ab", "d/e", "f.x", "f.y", "f.tmp"] {
+        backend.set(key, key).unwrap();
+    }
+    assert!(backend.set(&format!("g{TMP_SUFFIX}"), "x").is_err());
+    let keys = |prefix| {
+        let mut keys = backend.keys(prefix);
+        keys.sort();
+        keys
+    };
+    // Keys that only differ in their extension don't share a temporary file.
+    assert_eq!(backend.get("f.x").as_deref(), Some("f.x"));
+    assert_eq!(keys("f"), ["f.tmp", "f.x", "f.y"]);
+    assert_eq!(keys("a/b"), ["a/b/c", "a/bc"]);
+    assert_eq!(keys("a/b/"), ["a/b/c"]);
+    assert_eq!(keys("a"), ["a/b/c", "a/bc", "ab"]);
+    assert_eq!(keys("missing/"), Vec::<String>::new());
+    std::fs::remove_dir_all(root).unwrap();
+}
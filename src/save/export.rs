@@ -0,0 +1,187 @@
+//! Everything in the last successful frame of a slot as a single text file, e.g. for bug reports.
+//!
+//! Every line is a `key=value` pair, sorted by key. Backslashes and line breaks in values are escaped
+//! with a backslash. Empty lines and lines starting with `#` are ignored when importing.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io,
+};
+
+use super::{
+    migrate::{self, is_under},
+    slots::{self, SlotError},
+    storage,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    /// 1-based line number of a line that is not a `key=value` pair or contains an invalid escape.
+    Syntax(usize),
+    /// Keys that no part of the game uses.
+    Unknown(Vec<String>),
+    Slot(SlotError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(line) => write!(f, "line {line}: expected `key=value`"),
+            Self::Unknown(keys) => write!(f, "unknown keys: {}", keys.join(", ")),
+            Self::Slot(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<SlotError> for ImportError {
+    fn from(err: SlotError) -> Self {
+        Self::Slot(err)
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        Self::Slot(err.into())
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        result.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(result)
+}
+
+/// The last successful frame of the active slot.
+pub fn export() -> String {
+    storage::with(|storage| {
+        let slot = &storage.slot;
+        let mut text = format!("# solar_sailors save of slot `{slot}`\n");
+        if let Some(odd) = storage::read_odd(&*storage.backend, slot) {
+            let buffer = format!("{}{}/", slots::prefix(slot), odd as u8);
+            let mut keys = storage.backend.keys(&buffer);
+            keys.sort();
+            for key in keys {
+                let Some(value) = storage.backend.get(&key) else {
+                    continue;
+                };
+                text.push_str(&format!("{}={}\n", &key[buffer.len()..], escape(&value)));
+            }
+        }
+        text
+    })
+}
+
+/// Replace slot `slot` with an exported save, creating the slot if necessary.
+/// Exports of older versions are migrated first, then keys that are not used by anything that was
+/// loaded so far are rejected.
+/// On success, returns the keys that were loaded so far, but are missing from the export.
+/// They will keep their default values when `slot` is played.
+pub fn import(slot: &str, text: &str) -> Result<Vec<String>, ImportError> {
+    slots::check_name(slot)?;
+    let mut values = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or(ImportError::Syntax(i + 1))?;
+        let value = unescape(value).ok_or(ImportError::Syntax(i + 1))?;
+        values.insert(key.trim().to_owned(), value);
+    }
+    // Exports from older versions of the game use the keys of back then.
+    migrate::upgrade(&mut values, migrate::MIGRATIONS);
+    storage::with(|storage| {
+        if storage.slot == slot {
+            return Err(SlotError::Active(slot.to_owned()).into());
+        }
+        let mut missing = Vec::new();
+        // Nothing to compare with, e.g. in tools that don't load the game.
+        if !storage.keys.is_empty() {
+            let unknown: Vec<_> = values
+                .keys()
+                .filter(|key| !is_under(key, "meta"))
+                .filter(|key| !storage.keys.iter().any(|known| is_under(key, known)))
+                .cloned()
+                .collect();
+            if !unknown.is_empty() {
+                return Err(ImportError::Unknown(unknown));
+            }
+            missing = storage
+                .keys
+                .iter()
+                .filter(|known| !values.keys().any(|key| is_under(key, known)))
+                .cloned()
+                .collect();
+        }
+        let prefix = slots::prefix(slot);
+        storage.backend.remove_all(&prefix)?;
+        for (key, value) in &values {
+            storage.backend.set(&format!("{prefix}0/{key}"), value)?;
+        }
+        storage.backend.set(&format!("{prefix}odd"), "false")?;
+        Ok(missing)
+    })
+}
+
+#[test]
+fn roundtrip() {
+    use super::{backend::Memory, Saveable};
+
+    storage::set_backend(Memory::default());
+    let _time = Saveable::<f64>::new(0.0, "time");
+    let zoom = "ship/parts/0/0/4/zoom";
+    let _zoom = Saveable::<f32>::new(0.5, zoom);
+    let text = "# comment\ntime=12.5\nmeta/thumbnail=0,0 1,\\n\\\\\n";
+    assert_eq!(import("bug", text), Ok(vec![zoom.to_owned()]));
+    slots::select("bug").unwrap();
+    let exported = export();
+    assert_eq!(import("again", &exported), Ok(vec![zoom.to_owned()]));
+    assert_eq!(
+        exported.lines().skip(1).collect::<Vec<_>>(),
+        ["meta/schema=1", "meta/thumbnail=0,0 1,\\n\\\\", "time=12.5"]
+    );
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 12.5);
+
+    // Exports from before the map became a part of the ship get migrated.
+    assert_eq!(
+        import("old", "meta/schema=0\nmap_zoom=0.25"),
+        Ok(vec!["time".to_owned()])
+    );
+    storage::with(|storage| {
+        let get = |key: &str| storage.backend.get(&format!("slots/old/0/{key}"));
+        assert_eq!(get(zoom).as_deref(), Some("0.25"));
+        assert_eq!(get("map_zoom"), None);
+        assert_eq!(get(migrate::SCHEMA), Some(migrate::VERSION.to_string()));
+    });
+
+    assert_eq!(
+        import("other", "time=1\nplayer/x=3"),
+        Err(ImportError::Unknown(vec!["player/x".to_owned()]))
+    );
+    assert_eq!(import("other", "time"), Err(ImportError::Syntax(1)));
+    assert_eq!(
+        import("bug", "time=1"),
+        Err(ImportError::Slot(SlotError::Active("bug".to_owned())))
+    );
+}
@@ -0,0 +1,220 @@
+//! Older frames of a slot to go back to, e.g. after flinging the ship onto an escape trajectory.
+//!
+//! A snapshot is a full copy of a committed frame, stored below `slots/<name>/history/<kind>/<id>/`.
+//! Ids count up across all kinds, and only the newest [SnapshotKind::limit] snapshots of each kind
+//! are kept.
+
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+use macroquad::miniquad::date;
+
+use super::{backend::StorageBackend, slots, slots::SlotError, storage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// Taken on every [checkpoint](super::checkpoint), and before rolling back.
+    Checkpoint,
+    /// Taken once per hour of playing the slot.
+    Hourly,
+}
+
+impl SnapshotKind {
+    const ALL: [Self; 2] = [Self::Checkpoint, Self::Hourly];
+
+    /// How many snapshots of this kind are kept.
+    pub fn limit(self) -> usize {
+        match self {
+            Self::Checkpoint => 10,
+            Self::Hourly => 24,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Checkpoint => "checkpoint",
+            Self::Hourly => "hourly",
+        }
+    }
+}
+
+impl Display for SnapshotKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub id: usize,
+    pub kind: SnapshotKind,
+    /// When the frame was committed, in seconds since the unix epoch.
+    pub last_played: Option<f64>,
+    /// How long the slot had been played when the frame was committed, in seconds.
+    pub play_time: Option<f64>,
+    slot: String,
+}
+
+impl Snapshot {
+    /// Read `key` as it was when the snapshot was taken.
+    pub fn get(&self, key: &str) -> Option<String> {
+        storage::with(|storage| {
+            storage
+                .backend
+                .get(&format!("{}{key}", dir(&self.slot, self.kind, self.id)))
+        })
+    }
+}
+
+fn dir(slot: &str, kind: SnapshotKind, id: usize) -> String {
+    format!("{}history/{kind}/{id}/", slots::prefix(slot))
+}
+
+/// Kind and id of all snapshots of `slot`, oldest first.
+fn ids(backend: &dyn StorageBackend, slot: &str) -> Vec<(SnapshotKind, usize)> {
+    let prefix = format!("{}history/", slots::prefix(slot));
+    let mut ids: Vec<_> = backend
+        .keys(&prefix)
+        .into_iter()
+        .filter_map(|key| {
+            let mut segments = key[prefix.len()..].split('/');
+            let kind = segments.next()?;
+            let kind = SnapshotKind::ALL
+                .iter()
+                .copied()
+                .find(|k| k.name() == kind)?;
+            Some((kind, segments.next()?.parse().ok()?))
+        })
+        .collect();
+    ids.sort_by_key(|&(_, id)| id);
+    ids.dedup();
+    ids
+}
+
+/// Copy the last successful frame of `slot` into a new snapshot and forget the oldest snapshots
+/// of that kind.
+pub(super) fn take(
+    backend: &mut dyn StorageBackend,
+    slot: &str,
+    kind: SnapshotKind,
+) -> io::Result<()> {
+    let Some(odd) = storage::read_odd(backend, slot) else {
+        // Nothing to keep.
+        return Ok(());
+    };
+    let ids = ids(backend, slot);
+    let id = ids.last().map_or(0, |&(_, id)| id + 1);
+    let buffer = format!("{}{}/", slots::prefix(slot), odd as u8);
+    backend.copy_all(&buffer, &dir(slot, kind, id))?;
+    let old: Vec<_> = ids.into_iter().filter(|&(k, _)| k == kind).collect();
+    // The new snapshot isn't part of `old`.
+    for &(_, id) in &old[..(old.len() + 1).saturating_sub(kind.limit())] {
+        backend.remove_all(&dir(slot, kind, id))?;
+    }
+    Ok(())
+}
+
+/// All snapshots of the active slot, oldest first.
+pub fn list() -> Vec<Snapshot> {
+    storage::with(|storage| {
+        let slot = storage.slot.clone();
+        ids(&*storage.backend, &slot)
+            .into_iter()
+            .map(|(kind, id)| {
+                let time = |key| {
+                    storage
+                        .backend
+                        .get(&format!("{}{key}", dir(&slot, kind, id)))
+                        .and_then(|s| s.parse().ok())
+                };
+                Snapshot {
+                    last_played: time(slots::LAST_PLAYED),
+                    play_time: time(slots::PLAY_TIME),
+                    id,
+                    kind,
+                    slot: slot.clone(),
+                }
+            })
+            .collect()
+    })
+}
+
+/// Keep a copy of the frame committed at the end of the current frame, committing it right away
+/// instead of waiting for the next autosave.
+pub fn snapshot(kind: SnapshotKind) {
+    storage::with(|storage| {
+        if !storage.snapshots.contains(&kind) {
+            storage.snapshots.push(kind);
+        }
+    })
+}
+
+/// Make snapshot `id` the last successful frame of the active slot. The current frame becomes
+/// a [checkpoint](SnapshotKind::Checkpoint) first, so the rollback can be undone.
+/// Like selecting a slot, this must happen before anything gets loaded.
+pub fn rollback(id: usize) -> Result<(), SlotError> {
+    storage::with(|storage| {
+        assert!(!storage.transaction);
+        let slot = storage.slot.clone();
+        let backend = &mut *storage.backend;
+        let Some(&(kind, _)) = ids(backend, &slot).iter().find(|&&(_, i)| i == id) else {
+            return Err(SlotError::MissingSnapshot(id));
+        };
+        let prefix = slots::prefix(&slot);
+        let odd = storage::read_odd(backend, &slot).unwrap_or(true);
+        let next = format!("{prefix}{}/", (!odd) as u8);
+        backend.copy_all(&dir(&slot, kind, id), &next)?;
+        // Time spent playing is not undone.
+        if let Some(play_time) = storage::read(backend, &slot, slots::PLAY_TIME) {
+            backend.set(&format!("{next}{}", slots::PLAY_TIME), &play_time)?;
+        }
+        backend.set(
+            &format!("{next}{}", slots::LAST_PLAYED),
+            &date::now().to_string(),
+        )?;
+        take(backend, &slot, SnapshotKind::Checkpoint)?;
+        backend.set(&format!("{prefix}odd"), &(!odd).to_string())?;
+        // The snapshot may be from an older version of the game.
+        storage.migrated = false;
+        Ok(())
+    })
+}
+
+#[test]
+fn keep_bounded_history_and_roll_back() {
+    use super::{backend::Memory, Saveable};
+    use crate::datastructures::SetGet;
+
+    storage::set_backend(Memory::default());
+    let mut time = Saveable::<f64>::new(0.0, "time");
+    {
+        let mut transaction = storage::Transaction::start();
+        for t in 0..30 {
+            time.set(t as f64);
+            snapshot(SnapshotKind::Hourly);
+            if t % 2 == 0 {
+                snapshot(SnapshotKind::Checkpoint);
+            }
+            transaction.commit().unwrap();
+        }
+    }
+    let snapshots = list();
+    let count = |kind| snapshots.iter().filter(|s| s.kind == kind).count();
+    assert_eq!(count(SnapshotKind::Hourly), 24);
+    assert_eq!(count(SnapshotKind::Checkpoint), 10);
+    let oldest = &snapshots[0];
+    assert_eq!(oldest.kind, SnapshotKind::Hourly);
+    assert_eq!(oldest.get("time").as_deref(), Some("6"));
+    assert!(oldest.last_played.is_some());
+    assert!(oldest.play_time.is_some());
+
+    rollback(oldest.id).unwrap();
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 6.0);
+    // The frame before the rollback is the newest checkpoint now.
+    let newest = list().pop().unwrap();
+    assert_eq!(newest.kind, SnapshotKind::Checkpoint);
+    assert_eq!(newest.get("time").as_deref(), Some("29"));
+    assert_eq!(rollback(1000), Err(SlotError::MissingSnapshot(1000)));
+}
@@ -0,0 +1,197 @@
+//! Looking into the active slot without loading the game, e.g. for the `solar-save` tool.
+//!
+//! Nothing here migrates the slot, so everything is shown exactly as it is stored. Starting a
+//! [Transaction](storage::Transaction) to change the slot migrates it, just like in the game.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use super::{
+    migrate::{self, is_under},
+    schema, slots, storage,
+};
+
+/// All values of a frame, by key.
+pub type Frame = BTreeMap<String, String>;
+
+fn frame(storage: &storage::Storage, odd: bool) -> Frame {
+    let buffer = format!("{}{}/", slots::prefix(&storage.slot), odd as u8);
+    storage
+        .backend
+        .keys(&buffer)
+        .into_iter()
+        .filter_map(|key| {
+            let value = storage.backend.get(&key)?;
+            Some((key[buffer.len()..].to_owned(), value))
+        })
+        .collect()
+}
+
+/// The last successful frame of the active slot and the frame before it,
+/// or `None` if nothing was saved yet.
+pub fn frames() -> Option<(Frame, Frame)> {
+    storage::with(|storage| {
+        let odd = storage::read_odd(&*storage.backend, &storage.slot)?;
+        Some((frame(storage, odd), frame(storage, !odd)))
+    })
+}
+
+/// Something in the active slot that the game can't load as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Relative to the frame, except for the `odd` marker.
+    pub key: String,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Check the value of a key whose meaning is known to the save format itself.
+fn check(key: &str, value: &str) -> Result<(), String> {
+    let expected = |what: &str| Err(format!("expected {what}, found {value:?}"));
+    let number = |value: &str| value.parse::<f64>().is_ok();
+    match key {
+        migrate::SCHEMA => match value.parse::<usize>() {
+            Ok(version) if version > migrate::VERSION => Err(format!(
+                "schema version {version} is from a newer version of the game"
+            )),
+            Ok(_) => Ok(()),
+            Err(_) => expected("a schema version"),
+        },
+        slots::CREATED | slots::LAST_PLAYED | slots::PLAY_TIME if !number(value) => {
+            expected("a number")
+        }
+        slots::THUMBNAIL => {
+            let cell = |cell: &str| {
+                cell.split_once(',')
+                    .is_some_and(|(x, y)| x.parse::<i32>().is_ok() && y.parse::<i32>().is_ok())
+            };
+            if value.split_whitespace().all(cell) {
+                Ok(())
+            } else {
+                expected("`x,y` cells")
+            }
+        }
+        _ if key.ends_with("/len") && value.parse::<usize>().is_err() => expected("a length"),
+        _ if key.ends_with("/some") && value.parse::<bool>().is_err() => {
+            expected("`true` or `false`")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check the `odd` marker, the metadata and the bookkeeping of collections in the last successful
+/// frame of the active slot, and that all other values parse as their type in the [schema].
+pub fn validate() -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut problem = |key: &str, message: String| {
+        problems.push(Problem {
+            key: key.to_owned(),
+            message,
+        })
+    };
+    storage::with(|storage| {
+        let prefix = slots::prefix(&storage.slot);
+        let Some(marker) = storage.backend.get(&format!("{prefix}odd")) else {
+            // Nothing saved yet.
+            return;
+        };
+        if marker.trim().parse::<bool>().is_err() {
+            problem(
+                "odd",
+                format!("expected `true` or `false`, found {marker:?}"),
+            );
+        }
+        let odd = storage::read_odd(&*storage.backend, &storage.slot).unwrap();
+        let buffer = format!("{prefix}{}/", odd as u8);
+        let frame = frame(storage, odd);
+        for key in storage.backend.keys(&buffer) {
+            let key = &key[buffer.len()..];
+            if !frame.contains_key(key) {
+                problem(key, "unreadable".to_owned());
+            }
+        }
+        for (key, value) in &frame {
+            if let Err(message) = check(key, value) {
+                problem(key, message);
+                continue;
+            }
+            if let Some(schema) = schema::lookup(key) {
+                if !schema.parses(value) {
+                    problem(key, format!("expected a `{}`, found {value:?}", schema.ty));
+                    continue;
+                }
+            }
+            // Collections remove their old elements before saving new ones, so leftovers mean
+            // the frame was edited or only partially written.
+            let (collection, len) = if let Some(collection) = key.strip_suffix("/len") {
+                (collection, value.parse().unwrap())
+            } else if let Some(collection) = key.strip_suffix("/some") {
+                (collection, value.parse::<bool>().unwrap() as usize)
+            } else {
+                continue;
+            };
+            let some = key.ends_with("/some");
+            for element in frame.keys().filter(|element| is_under(element, collection)) {
+                let rest = &element[collection.len()..];
+                let index = rest.split('/').nth(1).unwrap_or_default();
+                let stale = if some {
+                    index == "value" && len == 0
+                } else {
+                    index.parse::<usize>().is_ok_and(|index| index >= len)
+                };
+                if stale {
+                    problem(element, format!("left over, `{key}` is {value}"));
+                }
+            }
+        }
+    });
+    problems
+}
+
+#[test]
+fn find_problems() {
+    use super::{backend::Memory, storage::Transaction};
+
+    storage::set_backend(Memory::default());
+    assert_eq!(validate(), []);
+    {
+        let mut transaction = Transaction::start();
+        for (key, value) in [
+            ("list/len", "1"),
+            ("list/0", "a"),
+            ("list/1", "b"),
+            ("option/some", "false"),
+            ("option/value/x", "1"),
+            ("map/len", "two"),
+            (slots::THUMBNAIL, "0,0 1"),
+            ("time", "anything"),
+            ("ship/parts/0/0/4/zoom", "0.5"),
+            ("player/side", "-1"),
+        ] {
+            storage::set(key, value);
+        }
+        transaction.commit().unwrap();
+    }
+    let problems: Vec<_> = validate().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        problems,
+        [
+            "list/1: left over, `list/len` is 1",
+            "map/len: expected a length, found \"two\"",
+            "meta/thumbnail: expected `x,y` cells, found \"0,0 1\"",
+            "option/value/x: left over, `option/some` is false",
+            "player/side: expected a `u8`, found \"-1\"",
+            "time: expected a `orbits::time::Time`, found \"anything\"",
+        ]
+    );
+    let (committed, previous) = frames().unwrap();
+    assert_eq!(committed["list/0"], "a");
+    assert!(previous.is_empty());
+}
@@ -0,0 +1,172 @@
+//! Keeping old saves loadable when keys get renamed or change their type.
+//!
+//! Every committed frame records the schema version it was written with. Before anything is loaded
+//! from a slot, all [MIGRATIONS] between that version and [VERSION] are applied to its last successful
+//! frame, and the result is committed as a new frame. So a crash during a migration just runs it
+//! again on the next start.
+
+use std::{collections::BTreeMap, io};
+
+use super::{backend::StorageBackend, slots, storage};
+
+/// One step of changes to the keys of a save.
+pub enum Migration {
+    /// Move a key and all its sub keys.
+    Rename {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Change the value of a key, e.g. from a float to an integer. Returning `None` removes the key.
+    Convert {
+        key: &'static str,
+        convert: fn(&str) -> Option<String>,
+    },
+    /// Remove a key and all its sub keys.
+    Drop(&'static str),
+}
+
+/// All migrations ever. Migrating from version `n` applies everything from index `n` on,
+/// so never remove or reorder entries, only append new ones.
+pub const MIGRATIONS: &[Migration] = &[
+    // The map became a part of the ship, which saves the state of all its parts.
+    Migration::Rename {
+        from: "map_zoom",
+        to: "ship/parts/0/0/4/zoom",
+    },
+];
+
+/// Schema version of saves written by this version of the game.
+pub const VERSION: usize = MIGRATIONS.len();
+
+/// Key of the schema version, saves without it are from before versioning, so version `0`.
+pub(super) const SCHEMA: &str = "meta/schema";
+
+/// Whether `key` is `prefix` itself or one of its sub keys.
+pub(super) fn is_under(key: &str, prefix: &str) -> bool {
+    key.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+impl Migration {
+    fn apply(&self, values: &mut BTreeMap<String, String>) {
+        match *self {
+            Self::Rename { from, to } => {
+                let keys: Vec<_> = values
+                    .keys()
+                    .filter(|key| is_under(key, from))
+                    .cloned()
+                    .collect();
+                for key in keys {
+                    let value = values.remove(&key).unwrap();
+                    values.insert(format!("{to}{}", &key[from.len()..]), value);
+                }
+            }
+            Self::Convert { key, convert } => {
+                if let Some(value) = values.remove(key) {
+                    if let Some(value) = convert(&value) {
+                        values.insert(key.to_owned(), value);
+                    }
+                }
+            }
+            Self::Drop(prefix) => values.retain(|key, _| !is_under(key, prefix)),
+        }
+    }
+}
+
+/// Apply the `migrations` that the frame with the given `values` is missing.
+/// Returns `false` if it was up to date already.
+pub(super) fn upgrade(values: &mut BTreeMap<String, String>, migrations: &[Migration]) -> bool {
+    let version: usize = values.get(SCHEMA).and_then(|v| v.parse().ok()).unwrap_or(0);
+    if version >= migrations.len() {
+        return false;
+    }
+    for migration in &migrations[version..] {
+        migration.apply(values);
+    }
+    values.insert(SCHEMA.to_owned(), migrations.len().to_string());
+    true
+}
+
+/// Bring the last successful frame of `slot` up to the newest schema version.
+pub(super) fn run(
+    backend: &mut dyn StorageBackend,
+    slot: &str,
+    migrations: &[Migration],
+) -> io::Result<()> {
+    let Some(odd) = storage::read_odd(backend, slot) else {
+        // Nothing saved yet.
+        return Ok(());
+    };
+    let prefix = slots::prefix(slot);
+    let buffer = format!("{prefix}{}/", odd as u8);
+    let mut values: BTreeMap<_, _> = backend
+        .keys(&buffer)
+        .into_iter()
+        .filter_map(|key| {
+            let value = backend.get(&key)?;
+            Some((key[buffer.len()..].to_owned(), value))
+        })
+        .collect();
+    if !upgrade(&mut values, migrations) {
+        return Ok(());
+    }
+    // Write the result into the other buffer and commit it, just like a regular frame.
+    let other = format!("{prefix}{}/", (!odd) as u8);
+    backend.remove_all(&other)?;
+    for (key, value) in &values {
+        backend.set(&format!("{other}{key}"), value)?;
+    }
+    backend.set(&format!("{prefix}odd"), &(!odd).to_string())?;
+    // Loading falls back to the frame before the last successful one, which must not contain
+    // values from before the migration.
+    backend.copy_all(&other, &buffer)
+}
+
+#[test]
+fn migrate_old_save() {
+    use super::backend::Memory;
+
+    let migrations = [
+        Migration::Rename {
+            from: "player",
+            to: "crew/0",
+        },
+        Migration::Convert {
+            key: "crew/0/side_pos",
+            convert: |value| Some((value.parse::<f32>().ok()? as i32).to_string()),
+        },
+        Migration::Drop("time"),
+    ];
+    let mut backend = Memory::default();
+    for (key, value) in [
+        ("odd", "true"),
+        ("1/player/x", "1"),
+        ("1/player/side_pos", "2.5"),
+        ("1/players", "3"),
+        ("1/time", "5"),
+        ("1/map_zoom", "0.5"),
+    ] {
+        backend.set(&format!("slots/default/{key}"), value).unwrap();
+    }
+    run(&mut backend, "default", &migrations).unwrap();
+    let get = |backend: &Memory, key: &str| backend.get(&format!("slots/default/0/{key}"));
+    assert_eq!(backend.keys("slots/default/0/").len(), 5);
+    assert_eq!(get(&backend, "crew/0/x").as_deref(), Some("1"));
+    assert_eq!(get(&backend, "crew/0/side_pos").as_deref(), Some("2"));
+    assert_eq!(get(&backend, "players").as_deref(), Some("3"));
+    assert_eq!(get(&backend, "map_zoom").as_deref(), Some("0.5"));
+    assert_eq!(get(&backend, SCHEMA).as_deref(), Some("3"));
+    assert_eq!(storage::read_odd(&backend, "default"), Some(false));
+    // Both buffers are migrated.
+    assert_eq!(backend.keys("slots/default/1/").len(), 5);
+    assert_eq!(
+        backend.get("slots/default/1/crew/0/side_pos").as_deref(),
+        Some("2")
+    );
+
+    // Only the migrations that are new get applied.
+    backend.set("slots/default/0/time", "6").unwrap();
+    run(&mut backend, "default", &migrations[..3]).unwrap();
+    assert_eq!(storage::read_odd(&backend, "default"), Some(false));
+    assert_eq!(get(&backend, "time").as_deref(), Some("6"));
+}
@@ -0,0 +1,114 @@
+//! The type of every single value the game saves, by key, so saves can be checked without loading
+//! the game, e.g. with the `solar-save` tool. The game warns when it loads a value that is missing
+//! here or has another type, so the schema doesn't silently fall behind.
+
+use std::{any::type_name, str::FromStr};
+
+use macroquad::logging::warn;
+use orbits::{orbits::Object, Time};
+
+/// A value at a key matching `key` that gets loaded as a `ty`.
+#[derive(Clone, Copy, Debug)]
+pub struct Value {
+    /// `*` matches any single path segment, e.g. the index of an element.
+    pub key: &'static str,
+    pub ty: &'static str,
+    parses: fn(&str) -> bool,
+}
+
+impl Value {
+    fn new<T: FromStr>(key: &'static str) -> Self {
+        fn parses<T: FromStr>(value: &str) -> bool {
+            value.parse::<T>().is_ok()
+        }
+        Self {
+            key,
+            ty: type_name::<T>(),
+            parses: parses::<T>,
+        }
+    }
+
+    /// Whether `value` can be loaded as a `ty`.
+    pub fn parses(&self, value: &str) -> bool {
+        (self.parses)(value)
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        let mut segments = key.split('/');
+        self.key.split('/').all(|pattern| {
+            segments
+                .next()
+                .is_some_and(|segment| pattern == "*" || pattern == segment)
+        }) && segments.next().is_none()
+    }
+}
+
+/// Enums are loaded as the name of their variant. Unknown names keep the current variant.
+type Variant = String;
+
+/// Everything the game saves, besides the metadata of the slot. Keys whose pattern matches several
+/// entries, like the `len` of a collection, use the first one.
+fn values() -> Vec<Value> {
+    vec![
+        Value::new::<Time>("time"),
+        Value::new::<usize>("orbits/objects/len"),
+        Value::new::<usize>("orbits/objects/*/id"),
+        Value::new::<Object>("orbits/objects/*/object"),
+        Value::new::<usize>("orbits/objects/*/maneuvers/len"),
+        Value::new::<Time>("orbits/objects/*/maneuvers/*/t"),
+        Value::new::<f64>("orbits/objects/*/maneuvers/*/dx"),
+        Value::new::<f64>("orbits/objects/*/maneuvers/*/dy"),
+        Value::new::<bool>("orbits/ship/some"),
+        Value::new::<usize>("orbits/ship/value/0"),
+        Value::new::<i32>("player/x"),
+        Value::new::<i32>("player/y"),
+        Value::new::<u8>("player/side"),
+        Value::new::<i32>("player/side_pos"),
+        Value::new::<Variant>("player/action/variant"),
+        Value::new::<bool>("player/action/Walk/right"),
+        Value::new::<bool>("player/action/Use/up"),
+        Value::new::<usize>("ship/layout/len"),
+        Value::new::<i32>("ship/layout/*/x"),
+        Value::new::<i32>("ship/layout/*/y"),
+        Value::new::<Variant>("ship/layout/*/content/variant"),
+        Value::new::<Variant>("ship/layout/*/attachements/*/variant"),
+        Value::new::<usize>("ship/parts/*/*/content/len"),
+        Value::new::<f32>("ship/parts/*/*/content/*"),
+        Value::new::<f32>("ship/parts/*/*/*/zoom"),
+        Value::new::<f32>("ship/parts/*/*/*/sail_width"),
+        Value::new::<f32>("ship/parts/*/*/*/left_rope"),
+        Value::new::<f32>("ship/parts/*/*/*/right_rope"),
+        Value::new::<f32>("ship/parts/*/*/*/angle"),
+        Value::new::<f32>("ship/parts/*/*/*/angular_velocity"),
+    ]
+}
+
+/// How the value at `key` gets loaded, or `None` if the game doesn't save anything there.
+pub fn lookup(key: &str) -> Option<Value> {
+    values().into_iter().find(|value| value.matches(key))
+}
+
+/// Warn if the game loads the value at `key` as something else than the schema says.
+pub(super) fn check<T>(key: &str) {
+    match lookup(key) {
+        Some(value) if value.ty == type_name::<T>() => {}
+        Some(value) => warn!(
+            "save key `{key}` is loaded as a `{}`, but the schema has a `{}`",
+            type_name::<T>(),
+            value.ty
+        ),
+        None => warn!("save key `{key}` is missing from the schema"),
+    }
+}
+
+#[test]
+fn patterns() {
+    assert_eq!(lookup("ship/parts/0/-1/4/zoom").unwrap().ty, "f32");
+    assert_eq!(lookup("ship/parts/0/0/content/len").unwrap().ty, "usize");
+    assert_eq!(lookup("ship/parts/0/0/content/2").unwrap().ty, "f32");
+    assert!(lookup("ship/parts/0/0/4").is_none());
+    assert!(lookup("time/0").is_none());
+    let action = lookup("player/action/variant").unwrap();
+    assert!(action.parses("Sleep"));
+    assert!(!lookup("player/side").unwrap().parses("-1"));
+}
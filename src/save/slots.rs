@@ -0,0 +1,331 @@
+//! Named save games.
+//!
+//! Every slot is a complete save game with its own two buffers, stored below `slots/<name>/`.
+//! Besides the game state, each committed frame also records some metadata about the slot,
+//! so a slot can be described without loading it.
+
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+use macroquad::miniquad::date;
+
+use super::{
+    backend::StorageBackend,
+    history::{self, SnapshotKind},
+    migrate, storage,
+};
+
+/// The slot that is played if no other slot was selected.
+pub const DEFAULT: &str = "default";
+
+/// Keys of the metadata, next to all the other keys of the game.
+pub(super) const CREATED: &str = "meta/created";
+pub(super) const LAST_PLAYED: &str = "meta/last_played";
+pub(super) const PLAY_TIME: &str = "meta/play_time";
+pub(super) const THUMBNAIL: &str = "meta/thumbnail";
+
+pub(super) fn prefix(slot: &str) -> String {
+    format!("slots/{slot}/")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotInfo {
+    pub name: String,
+    /// Seconds since the unix epoch.
+    pub created: Option<f64>,
+    /// Seconds since the unix epoch.
+    pub last_played: Option<f64>,
+    /// Total time spent playing in this slot, in seconds.
+    pub play_time: f64,
+    /// Hex coordinates of all segments of the ship.
+    pub thumbnail: Vec<(i32, i32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotError {
+    /// Empty, `.`, `..`, containing `/`, `\` or control characters, or ending in `.tmp` or `.tmp~`.
+    InvalidName(String),
+    Exists(String),
+    Missing(String),
+    /// The slot currently being played can't be renamed or deleted.
+    Active(String),
+    /// The active slot has no snapshot with this id.
+    MissingSnapshot(usize),
+    /// Writing to the storage failed.
+    Storage(String),
+}
+
+impl Display for SlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "`{name}` is not a valid slot name"),
+            Self::Exists(name) => write!(f, "slot `{name}` already exists"),
+            Self::Missing(name) => write!(f, "slot `{name}` does not exist"),
+            Self::Active(name) => write!(f, "slot `{name}` is being played"),
+            Self::MissingSnapshot(id) => write!(f, "there is no snapshot {id}"),
+            Self::Storage(err) => write!(f, "could not write the save: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SlotError {}
+
+impl From<io::Error> for SlotError {
+    fn from(err: io::Error) -> Self {
+        Self::Storage(err.to_string())
+    }
+}
+
+fn exists(storage: &storage::Storage, name: &str) -> bool {
+    !storage.backend.keys(&prefix(name)).is_empty()
+}
+
+pub(super) fn check_name(name: &str) -> Result<(), SlotError> {
+    // Names are directories with the file system backend, so they must not lead out of `slots/`
+    // or look like a file that is being written.
+    let invalid = matches!(name, "" | "." | "..")
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control)
+        || name.ends_with(".tmp")
+        || name.ends_with(".tmp~");
+    if invalid {
+        return Err(SlotError::InvalidName(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// All slots, sorted by name.
+pub fn list() -> Vec<SlotInfo> {
+    storage::with(|storage| {
+        let mut names: Vec<_> = storage
+            .backend
+            .keys("slots/")
+            .into_iter()
+            .filter_map(|key| Some(key["slots/".len()..].split_once('/')?.0.to_owned()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| {
+                let read = |key| storage::read(&*storage.backend, &name, key);
+                let time = |key| read(key).and_then(|s| s.parse().ok());
+                let thumbnail = read(THUMBNAIL)
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .filter_map(|cell| {
+                        let (x, y) = cell.split_once(',')?;
+                        Some((x.parse().ok()?, y.parse().ok()?))
+                    })
+                    .collect();
+                SlotInfo {
+                    created: time(CREATED),
+                    last_played: time(LAST_PLAYED),
+                    play_time: time(PLAY_TIME).unwrap_or(0.0),
+                    thumbnail,
+                    name,
+                }
+            })
+            .collect()
+    })
+}
+
+/// `base` if there is no slot with that name yet, otherwise `base-2`, `base-3`, ... whichever
+/// is the first one that doesn't exist.
+pub fn unused_name(base: &str) -> String {
+    storage::with(|storage| {
+        std::iter::once(base.to_owned())
+            .chain((2..).map(|n| format!("{base}-{n}")))
+            .find(|name| !exists(storage, name))
+            .unwrap()
+    })
+}
+
+/// The slot everything is currently saved to.
+pub fn active() -> String {
+    storage::with(|storage| storage.slot.clone())
+}
+
+/// Play in slot `name` from now on, creating it if it doesn't exist yet.
+/// Must happen before anything gets loaded.
+pub fn select(name: &str) -> Result<(), SlotError> {
+    check_name(name)?;
+    if !storage::with(|storage| exists(storage, name)) {
+        create(name)?;
+    }
+    storage::with(|storage| {
+        assert!(!storage.transaction);
+        storage.slot = name.to_owned();
+        storage.migrated = false;
+    });
+    Ok(())
+}
+
+/// Create an empty slot.
+pub fn create(name: &str) -> Result<(), SlotError> {
+    check_name(name)?;
+    storage::with(|storage| {
+        if exists(storage, name) {
+            return Err(SlotError::Exists(name.to_owned()));
+        }
+        let prefix = prefix(name);
+        start(&mut *storage.backend, &format!("{prefix}0/"))?;
+        storage.backend.set(&format!("{prefix}odd"), "false")?;
+        Ok(())
+    })
+}
+
+/// Write the frame of a new game into `buffer`.
+fn start(backend: &mut dyn StorageBackend, buffer: &str) -> io::Result<()> {
+    backend.remove_all(buffer)?;
+    backend.set(&format!("{buffer}{CREATED}"), &date::now().to_string())?;
+    // Nothing to migrate in an empty frame.
+    backend.set(
+        &format!("{buffer}{}", migrate::SCHEMA),
+        &migrate::VERSION.to_string(),
+    )
+}
+
+/// Start a new game in the active slot. The current frame is kept as a
+/// [checkpoint](SnapshotKind::Checkpoint), so starting over can be undone.
+/// Like selecting a slot, this must happen before anything gets loaded.
+pub fn reset() -> Result<(), SlotError> {
+    storage::with(|storage| {
+        assert!(!storage.transaction);
+        let slot = storage.slot.clone();
+        let backend = &mut *storage.backend;
+        let prefix = prefix(&slot);
+        let odd = storage::read_odd(backend, &slot).unwrap_or(true);
+        start(backend, &format!("{prefix}{}/", (!odd) as u8))?;
+        history::take(backend, &slot, SnapshotKind::Checkpoint)?;
+        backend.set(&format!("{prefix}odd"), &(!odd).to_string())?;
+        Ok(())
+    })
+}
+
+/// Create slot `to` with the same contents as slot `from`.
+pub fn copy(from: &str, to: &str) -> Result<(), SlotError> {
+    check_name(from)?;
+    check_name(to)?;
+    storage::with(|storage| {
+        if !exists(storage, from) {
+            return Err(SlotError::Missing(from.to_owned()));
+        }
+        if exists(storage, to) {
+            return Err(SlotError::Exists(to.to_owned()));
+        }
+        storage.backend.copy_all(&prefix(from), &prefix(to))?;
+        Ok(())
+    })
+}
+
+pub fn rename(from: &str, to: &str) -> Result<(), SlotError> {
+    if storage::with(|storage| storage.slot == from) {
+        return Err(SlotError::Active(from.to_owned()));
+    }
+    copy(from, to)?;
+    storage::with(|storage| storage.backend.remove_all(&prefix(from)))?;
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), SlotError> {
+    check_name(name)?;
+    storage::with(|storage| {
+        if storage.slot == name {
+            return Err(SlotError::Active(name.to_owned()));
+        }
+        if !exists(storage, name) {
+            return Err(SlotError::Missing(name.to_owned()));
+        }
+        storage.backend.remove_all(&prefix(name))?;
+        Ok(())
+    })
+}
+
+/// Store the layout of the ship in the metadata of the active slot with the next commit.
+pub fn set_thumbnail(cells: impl IntoIterator<Item = (i32, i32)>) {
+    let cells: Vec<_> = cells.into_iter().map(|(x, y)| format!("{x},{y}")).collect();
+    storage::with(|storage| storage.thumbnail = Some(cells.join(" ")));
+}
+
+#[test]
+fn manage_slots() {
+    use super::backend::{Memory, StorageBackend as _};
+
+    // Saves from before there were slots end up in the default slot.
+    let mut legacy = Memory::default();
+    legacy.set("odd", "true").unwrap();
+    legacy.set("1/time", "5").unwrap();
+    legacy.set("other_app", "keep").unwrap();
+    storage::set_backend(legacy);
+    storage::with(|storage| {
+        assert_eq!(storage.backend.get("other_app").as_deref(), Some("keep"));
+        assert_eq!(storage.backend.get("odd"), None);
+    });
+    assert_eq!(active(), DEFAULT);
+    assert_eq!(storage::get("time").as_deref(), Some("5"));
+    assert!(storage::get(CREATED).is_some());
+
+    create("second").unwrap();
+    assert_eq!(create("second"), Err(SlotError::Exists("second".into())));
+    for name in ["", ".", "..", "a/b", "a\\b", "a\nb", "a.tmp", "a.tmp~"] {
+        assert_eq!(create(name), Err(SlotError::InvalidName(name.into())));
+        assert_eq!(select(name), Err(SlotError::InvalidName(name.into())));
+    }
+    assert_eq!(
+        copy("second", ".."),
+        Err(SlotError::InvalidName("..".into()))
+    );
+    assert_eq!(rename("..", "x"), Err(SlotError::InvalidName("..".into())));
+    assert_eq!(delete(".."), Err(SlotError::InvalidName("..".into())));
+    assert_eq!(unused_name("second"), "second-2");
+    assert_eq!(unused_name("fifth"), "fifth");
+    copy("second", "third").unwrap();
+    rename("third", "fourth").unwrap();
+    assert_eq!(delete(DEFAULT), Err(SlotError::Active(DEFAULT.into())));
+    assert_eq!(delete("third"), Err(SlotError::Missing("third".into())));
+    select("fourth").unwrap();
+    delete(DEFAULT).unwrap();
+    delete("second").unwrap();
+
+    set_thumbnail([(0, 0), (0, -1)]);
+    let mut transaction = storage::Transaction::start();
+    storage::set("time", "7");
+    transaction.commit().unwrap();
+    drop(transaction);
+    let slots = list();
+    assert_eq!(slots.len(), 1);
+    assert_eq!(slots[0].name, "fourth");
+    assert!(slots[0].created.is_some());
+    assert!(slots[0].last_played.is_some());
+    assert_eq!(slots[0].thumbnail, [(0, 0), (0, -1)]);
+    assert_eq!(storage::get("time").as_deref(), Some("7"));
+}
+
+#[test]
+fn start_over() {
+    use super::backend::Memory;
+
+    storage::set_backend(Memory::default());
+    {
+        let mut transaction = storage::Transaction::start();
+        storage::set("time", "5");
+        transaction.commit().unwrap();
+    }
+    // The default slot comes into existence with the first commit.
+    let created = storage::get(CREATED).unwrap();
+    assert_eq!(list()[0].created, created.parse().ok());
+    reset().unwrap();
+    assert_eq!(storage::get("time"), None);
+    assert!(storage::get(CREATED).is_some());
+    assert_eq!(
+        storage::get(migrate::SCHEMA),
+        Some(migrate::VERSION.to_string())
+    );
+    // The old game can still be gone back to.
+    let snapshots = history::list();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].get("time").as_deref(), Some("5"));
+}
@@ -1,109 +1,724 @@
-#[cfg(not(target_arch = "wasm32"))]
-use std::path::PathBuf;
 use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
     future::Future,
-    sync::atomic::{AtomicBool, Ordering},
+    io,
 };
 
-pub fn set(key: &str, value: &str) {
-    assert!(TRANSACTION.load(Ordering::Relaxed));
-    let odd = ODD.load(Ordering::Relaxed);
-    set_inner(&format!("{}/{}", odd as u8, key), value)
+use macroquad::{
+    input::{is_quit_requested, prevent_quit},
+    logging::warn,
+    miniquad::date,
+};
+
+use super::{
+    backend::{self, StorageBackend},
+    history::{self, SnapshotKind},
+    migrate, slots,
+};
+
+pub(super) struct Storage {
+    pub(super) backend: Box<dyn StorageBackend>,
+    /// Whether nothing may be written, not even to migrate the slot.
+    read_only: bool,
+    pub(super) transaction: bool,
+    /// The save slot everything is read from and written to.
+    pub(super) slot: String,
+    /// Values written since the last commit, by key. `None` removes the key and all keys below it.
+    journal: BTreeMap<String, Option<String>>,
+    /// Written into the metadata of the slot with the next commit.
+    pub(super) thumbnail: Option<String>,
+    /// Keys of everything that was loaded so far.
+    pub(super) keys: BTreeSet<String>,
+    /// Whether migrating the slot to the newest schema version was attempted yet.
+    pub(super) migrated: bool,
+    /// Why committing the last frame failed, until a frame gets committed successfully again.
+    error: Option<String>,
+    /// Seconds between two commits of [transaction_loop].
+    interval: f64,
+    /// Snapshots to take of the frame committed at the end of the current frame. Requesting any
+    /// commits it no matter how long ago the last commit was.
+    pub(super) snapshots: Vec<SnapshotKind>,
+    /// Whether [transaction_loop] should return after the current frame.
+    reload: bool,
 }
 
-fn set_inner(key: &str, value: &str) {
-    #[cfg(target_arch = "wasm32")]
-    {
-        quad_storage_sys::set(key, value)
+/// Default of [set_autosave_interval].
+#[cfg(not(target_arch = "wasm32"))]
+const AUTOSAVE_INTERVAL: f64 = 5.0;
+/// Browsers close tabs without giving the game a chance to save, so every frame gets committed.
+/// Frames without changes write nothing, so this doesn't hammer the `localStorage`.
+#[cfg(target_arch = "wasm32")]
+const AUTOSAVE_INTERVAL: f64 = 0.0;
+
+/// Seconds of play time between two [hourly snapshots](SnapshotKind::Hourly).
+const HOUR: f64 = 3600.0;
+
+impl Storage {
+    /// Only a `read_only` storage leaves a save game from before there were slots where it is.
+    fn new(mut backend: Box<dyn StorageBackend>, read_only: bool) -> Self {
+        // Before there were slots, the only save game was at the root.
+        if !read_only && backend.get("odd").is_some() {
+            let prefix = slots::prefix(slots::DEFAULT);
+            // Only what the old layout wrote, other keys may belong to someone else, e.g. in the
+            // `localStorage` shared by everything on the same website. The marker goes last, so a
+            // partially moved save still gets recognized as one.
+            let mut keys = backend.keys("0/");
+            keys.extend(backend.keys("1/"));
+            keys.push("odd".to_owned());
+            let moved = keys.into_iter().try_for_each(|key| {
+                if let Some(value) = backend.get(&key) {
+                    backend.set(&format!("{prefix}{key}"), &value)?;
+                }
+                backend.remove(&key)
+            });
+            // Whatever was not moved yet stays where it is and gets moved on the next start.
+            let created = moved.and_then(|()| {
+                // The old save doesn't know when it was created, so the slot was created now.
+                let odd = read_odd(&*backend, slots::DEFAULT).unwrap_or(true);
+                let key = format!("{prefix}{}/{}", odd as u8, slots::CREATED);
+                match backend.get(&key) {
+                    Some(_) => Ok(()),
+                    None => backend.set(&key, &date::now().to_string()),
+                }
+            });
+            if let Err(err) = created {
+                warn!("could not move the save game into the default slot: {err}");
+            }
+        }
+        if read_only {
+            backend = Box::new(backend::ReadOnly(backend));
+        }
+        Self {
+            backend,
+            read_only,
+            transaction: false,
+            slot: slots::DEFAULT.to_owned(),
+            journal: BTreeMap::new(),
+            thumbnail: None,
+            keys: BTreeSet::new(),
+            migrated: false,
+            error: None,
+            interval: AUTOSAVE_INTERVAL,
+            snapshots: Vec::new(),
+            reload: false,
+        }
     }
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let path = path(key);
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent).unwrap();
+
+    /// Run all pending migrations of the slot, before anything gets loaded from it.
+    /// If that fails, the slot keeps its old schema version and gets migrated on the next start.
+    fn migrate(&mut self) {
+        if !self.migrated && !self.read_only {
+            if let Err(err) = migrate::run(&mut *self.backend, &self.slot, migrate::MIGRATIONS) {
+                warn!("could not migrate save slot `{}`: {err}", self.slot);
             }
+            self.migrated = true;
         }
-        std::fs::write(path, value).unwrap();
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn path(key: &str) -> PathBuf {
-    let mut path = directories::ProjectDirs::from("", "", "solar_sailors")
-        .map(|dirs| dirs.data_local_dir().to_owned())
-        .unwrap_or_default();
+thread_local! {
+    /// Only created when it is first used, so choosing a backend before that never touches the
+    /// storage of the platform.
+    static STORAGE: RefCell<Option<Storage>> = const { RefCell::new(None) };
+}
 
-    for elem in key.split('/') {
-        path.push(elem);
-    }
-    path
+pub(super) fn with<R>(f: impl FnOnce(&mut Storage) -> R) -> R {
+    STORAGE.with_borrow_mut(|storage| {
+        f(storage.get_or_insert_with(|| Storage::new(backend::platform(), false)))
+    })
+}
+
+/// Choose where the game gets saved. Must happen before anything gets loaded.
+pub fn set_backend(backend: impl StorageBackend + 'static) {
+    STORAGE.with_borrow_mut(|storage| {
+        assert!(!storage.as_ref().is_some_and(|storage| storage.transaction));
+        *storage = Some(Storage::new(Box::new(backend), false));
+    })
+}
+
+/// Like [set_backend], but to only look at the saves, e.g. in a tool. Nothing gets moved or
+/// migrated, so everything is seen exactly as it is stored, and all writes fail.
+pub fn set_read_only_backend(backend: impl StorageBackend + 'static) {
+    STORAGE.with_borrow_mut(|storage| {
+        assert!(!storage.as_ref().is_some_and(|storage| storage.transaction));
+        *storage = Some(Storage::new(Box::new(backend), true));
+    })
+}
+
+/// How many seconds [transaction_loop] waits between commits. Everything saved in between is only kept
+/// in memory, and lost if the game crashes. `0.0` commits every frame.
+pub fn set_autosave_interval(seconds: f64) {
+    with(|storage| storage.interval = seconds);
+}
+
+/// Commit everything at the end of the current frame instead of waiting for the next autosave,
+/// e.g. right after something the player would hate to lose. The frame is kept as a
+/// [checkpoint snapshot](SnapshotKind::Checkpoint).
+pub fn checkpoint() {
+    history::snapshot(SnapshotKind::Checkpoint);
+}
+
+/// Make [transaction_loop] commit and return after the current frame, so everything can be loaded
+/// again, e.g. after a [rollback](history::rollback).
+pub fn reload() {
+    with(|storage| storage.reload = true);
+}
+
+/// Remember that something uses `key` and all keys below it.
+pub fn register(key: &str) {
+    with(|storage| storage.keys.insert(key.to_owned()));
+}
+
+/// Only records the value, it is written to storage when the current frame gets committed.
+pub fn set(key: &str, value: &str) {
+    with(|storage| {
+        assert!(storage.transaction);
+        storage
+            .journal
+            .insert(key.to_owned(), Some(value.to_owned()));
+    })
+}
+
+/// Remove `key` and all keys below it with the current frame.
+/// Keys below it that are set again later in the same frame are kept.
+pub fn remove(key: &str) {
+    with(|storage| {
+        assert!(storage.transaction);
+        storage
+            .journal
+            .retain(|journaled, _| !migrate::is_under(journaled, key));
+        storage.journal.insert(key.to_owned(), None);
+    })
 }
 
 pub fn get(key: &str) -> Option<String> {
-    // Only do it while in the "loading" stage, not during the game itself,
-    // as you may get inconsistent state.
-    assert!(!TRANSACTION.load(Ordering::Relaxed));
-    // Always read from the last successful frame.
-    // If there was no previous successful frame, immediately bail out, there can't
-    // be any actual values anyway.
-    let odd: bool = get_inner("odd")?.parse().unwrap();
-    get_inner(&format!("{}/{}", odd as u8, key))
-}
-
-fn get_inner(key: &str) -> Option<String> {
-    #[cfg(target_arch = "wasm32")]
-    {
-        quad_storage_sys::get(key)
-    }
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        let path = path(key);
-        std::fs::read_to_string(path).ok()
+    with(|storage| {
+        // Only do it while in the "loading" stage, not during the game itself,
+        // as you may get inconsistent state.
+        assert!(!storage.transaction);
+        // Always read from the last successful frame.
+        // If there was no previous successful frame, immediately bail out, there can't
+        // be any actual values anyway.
+        storage.migrate();
+        read(&*storage.backend, &storage.slot, key)
+    })
+}
+
+/// Like [get], but from the frame before the last successful one.
+/// For when the value in the last successful frame is corrupt.
+pub fn get_previous(key: &str) -> Option<String> {
+    with(|storage| {
+        assert!(!storage.transaction);
+        let odd = read_odd(&*storage.backend, &storage.slot)?;
+        let prefix = slots::prefix(&storage.slot);
+        storage
+            .backend
+            .get(&format!("{prefix}{}/{key}", (!odd) as u8))
+    })
+}
+
+/// All keys at or below `key` in the last successful frame, e.g. to check how many elements of a
+/// collection there really are.
+pub fn keys(key: &str) -> Vec<String> {
+    with(|storage| {
+        assert!(!storage.transaction);
+        storage.migrate();
+        let Some(odd) = read_odd(&*storage.backend, &storage.slot) else {
+            return Vec::new();
+        };
+        let frame = format!("{}{}/", slots::prefix(&storage.slot), odd as u8);
+        storage
+            .backend
+            .keys(&format!("{frame}{key}"))
+            .into_iter()
+            .map(|stored| stored[frame.len()..].to_owned())
+            .filter(|stored| migrate::is_under(stored, key))
+            .collect()
+    })
+}
+
+/// Why committing the last frame failed, or `None` if saving works.
+pub fn error() -> Option<String> {
+    with(|storage| storage.error.clone())
+}
+
+/// Read `key` from the last successful frame of `slot`.
+pub(super) fn read(backend: &dyn StorageBackend, slot: &str, key: &str) -> Option<String> {
+    let odd = read_odd(backend, slot)?;
+    backend.get(&format!("{}{}/{key}", slots::prefix(slot), odd as u8))
+}
+
+/// Which buffer of `slot` belongs to the last successful frame,
+/// or `None` if there never was one.
+pub(super) fn read_odd(backend: &dyn StorageBackend, slot: &str) -> Option<bool> {
+    let prefix = slots::prefix(slot);
+    let odd = backend.get(&format!("{prefix}odd"))?;
+    match odd.trim().parse() {
+        Ok(odd) => Some(odd),
+        Err(_) => {
+            // Every frame records when it was committed, so pick the newer one.
+            let last_played = |odd: bool| {
+                backend
+                    .get(&format!("{prefix}{}/{}", odd as u8, slots::LAST_PLAYED))
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(f64::NEG_INFINITY)
+            };
+            let odd = last_played(true) >= last_played(false);
+            warn!(
+                "save slot `{slot}` has a corrupt `odd` marker, using buffer {}",
+                odd as u8
+            );
+            Some(odd)
+        }
     }
 }
 
-static ODD: AtomicBool = AtomicBool::new(false);
-static TRANSACTION: AtomicBool = AtomicBool::new(false);
+/// The two buffers and which one belongs to the last successful frame.
+///
+/// Each frame only writes to the other buffer and then flips the `odd` marker, so a crash never leaves
+/// a half written frame behind. Instead of copying the whole save into the other buffer every frame,
+/// only the keys changed in this frame and the previous frame are written, as the other buffer
+/// is exactly one frame behind.
+pub struct Transaction {
+    /// Where the buffers and the `odd` marker of the current slot are.
+    prefix: String,
+    odd: bool,
+    /// Changes of the last successful frame, which the other buffer is missing.
+    previous: BTreeMap<String, Option<String>>,
+    /// Play time of the slot before this session, in seconds.
+    play_time: f64,
+    /// When this session started, in seconds since the unix epoch.
+    started: f64,
+    /// Whether the other buffer was brought up to date with the last successful frame yet.
+    synced: bool,
+    /// When the last commit was attempted, in seconds since the unix epoch.
+    committed: f64,
+    /// Full hours of play time of the slot in the last successful frame.
+    hours: f64,
+}
 
-pub async fn transaction_loop<F: Future<Output = ()>>(mut f: impl FnMut() -> F) {
-    assert_eq!(
-        TRANSACTION.compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed),
-        Ok(false)
-    );
-    // Figure out the last successfull transaction.
-    let mut odd = get_inner("odd").map(|s| s.parse().unwrap()).unwrap_or(true);
-    loop {
-        // Use the next frame.
-        odd = !odd;
-        // Preserve previous state.
-
-        #[cfg(target_arch = "wasm32")]
-        {
-            use quad_storage_sys::*;
-            let oddstr = format!("{}/", (!odd) as u8);
-            for i in 0..len() {
-                let key = key(i).unwrap();
-                if key.starts_with(&oddstr) {
-                    let new_key = format!("{}/{}", odd as u8, &key[2..]);
-                    let val = get(&key).unwrap();
-                    set(&new_key, &val);
+impl Transaction {
+    pub fn start() -> Self {
+        with(|storage| {
+            assert!(!storage.transaction);
+            storage.transaction = true;
+            storage.migrate();
+            // Figure out the last successfull transaction.
+            let odd = read_odd(&*storage.backend, &storage.slot);
+            // New saves are created with the newest schema, too. Existing ones got it by migrating them.
+            if odd.is_none() {
+                storage.journal.insert(
+                    migrate::SCHEMA.to_owned(),
+                    Some(migrate::VERSION.to_string()),
+                );
+                storage
+                    .journal
+                    .insert(slots::CREATED.to_owned(), Some(date::now().to_string()));
+            }
+            let play_time = read(&*storage.backend, &storage.slot, slots::PLAY_TIME)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.0);
+            Self {
+                prefix: slots::prefix(&storage.slot),
+                odd: odd.unwrap_or(true),
+                previous: BTreeMap::new(),
+                play_time,
+                started: date::now(),
+                synced: false,
+                committed: date::now(),
+                hours: (play_time / HOUR).floor(),
+            }
+        })
+    }
+
+    /// If this fails, nothing of the frame is visible and it gets retried together with the next frame.
+    pub fn commit(&mut self) -> io::Result<()> {
+        with(|storage| {
+            self.committed = date::now();
+            let mut journal = std::mem::take(&mut storage.journal);
+            if let Some(thumbnail) = storage.thumbnail.take() {
+                journal.insert(slots::THUMBNAIL.to_owned(), Some(thumbnail));
+            }
+            let mut changes = self.changes(&*storage.backend, &journal);
+            // Without changes, the last successful frame is still up to date.
+            if !changes.is_empty() {
+                let now = date::now();
+                let play_time = self.play_time + now - self.started;
+                changes.insert(slots::LAST_PLAYED.to_owned(), Some(now.to_string()));
+                changes.insert(slots::PLAY_TIME.to_owned(), Some(play_time.to_string()));
+                if let Err(err) = self.write(&mut *storage.backend, &changes) {
+                    storage.journal = journal;
+                    return Err(err);
+                }
+                // Use the next frame.
+                self.odd = !self.odd;
+                self.previous = changes;
+                let hours = (play_time / HOUR).floor();
+                if hours > self.hours && !storage.snapshots.contains(&SnapshotKind::Hourly) {
+                    storage.snapshots.push(SnapshotKind::Hourly);
                 }
+                self.hours = hours;
             }
+            for kind in std::mem::take(&mut storage.snapshots) {
+                // The frame itself is safe, so losing a snapshot is no reason to fail it.
+                if let Err(err) = history::take(&mut *storage.backend, &storage.slot, kind) {
+                    warn!("could not take a {kind} snapshot: {err}");
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// [Commit](Self::commit) if a snapshot was requested or the autosave interval passed.
+    pub fn autosave(&mut self) -> io::Result<()> {
+        let due = with(|storage| {
+            !storage.snapshots.is_empty() || date::now() - self.committed >= storage.interval
+        });
+        if due {
+            self.commit()?;
         }
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let dest = path(&(odd as u8).to_string());
-            // Also work if there wasn't a save game yet so no folders, either
-            if std::fs::remove_dir_all(&dest).is_ok() {
-                dircpy::copy_dir(path(&((!odd) as u8).to_string()), dest).unwrap();
+        Ok(())
+    }
+
+    /// The part of `journal` that differs from the last successful frame. Values that are set to
+    /// what they already are get left out, and removals only remove the keys that exist and don't
+    /// get set again, so saving a whole collection only writes what changed.
+    fn changes(
+        &self,
+        backend: &dyn StorageBackend,
+        journal: &BTreeMap<String, Option<String>>,
+    ) -> BTreeMap<String, Option<String>> {
+        let current = format!("{}{}/", self.prefix, self.odd as u8);
+        let mut changes = BTreeMap::new();
+        for (key, value) in journal {
+            match value {
+                Some(value) => {
+                    if backend.get(&format!("{current}{key}")).as_ref() != Some(value) {
+                        changes.insert(key.clone(), Some(value.clone()));
+                    }
+                }
+                None => {
+                    for stored in backend.keys(&format!("{current}{key}")) {
+                        let stored = &stored[current.len()..];
+                        if migrate::is_under(stored, key) && !journal.contains_key(stored) {
+                            changes.insert(stored.to_owned(), None);
+                        }
+                    }
+                }
             }
         }
-        // Let all the regular storage ops know what prefix to use.
-        ODD.store(odd, Ordering::Relaxed);
+        changes
+    }
+
+    /// Write the changes of the previous and the current frame into the other buffer and make it
+    /// the last successful frame.
+    fn write(
+        &mut self,
+        backend: &mut dyn StorageBackend,
+        journal: &BTreeMap<String, Option<String>>,
+    ) -> io::Result<()> {
+        let next = format!("{}{}/", self.prefix, (!self.odd) as u8);
+        if !self.synced {
+            // We don't know how far behind the other buffer is, e.g. after a crash, so bring it up to date once.
+            backend.copy_all(&format!("{}{}/", self.prefix, self.odd as u8), &next)?;
+            self.synced = true;
+        }
+        let removed: Vec<_> = journal
+            .iter()
+            .filter(|(_, value)| value.is_none())
+            .map(|(key, _)| key)
+            .collect();
+        let previous = self.previous.iter().filter(|(key, _)| {
+            !journal.contains_key(*key)
+                && !removed
+                    .iter()
+                    .any(|removed| migrate::is_under(key, removed))
+        });
+        let changes: Vec<_> = previous.chain(journal).collect();
+        // Anything set in the same frame as a removal was set after it.
+        for (key, _) in changes.iter().filter(|(_, value)| value.is_none()) {
+            backend.remove(&format!("{next}{key}"))?;
+            backend.remove_all(&format!("{next}{key}/"))?;
+        }
+        for (key, value) in changes {
+            if let Some(value) = value {
+                backend.set(&format!("{next}{key}"), value)?;
+            }
+        }
+        // Transaction successfully done
+        backend.set(&format!("{}odd", self.prefix), &(!self.odd).to_string())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        with(|storage| {
+            storage.transaction = false;
+            // Anything saved after the last commit is part of a frame that never finished.
+            storage.journal.clear();
+            storage.snapshots.clear();
+        })
+    }
+}
+
+/// Why [transaction_loop] returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// The player closed the game.
+    Quit,
+    /// [reload] was called.
+    Reload,
+}
+
+/// Runs `f` once per frame, committing everything it saved at the end of a frame whenever the autosave
+/// interval passed or a [checkpoint] was requested. Commits once more and returns when the player quits
+/// or [reload] was called.
+pub async fn transaction_loop<F: Future<Output = ()>>(mut f: impl FnMut() -> F) -> Exit {
+    // Closing the window only sets a flag, so the last changes can still be saved.
+    prevent_quit();
+    let mut transaction = Transaction::start();
+    loop {
         // Perform transaction
         f().await;
-        // Transaction successfully done
-        set_inner("odd", &odd.to_string());
+        let exit = if is_quit_requested() {
+            Some(Exit::Quit)
+        } else {
+            with(|storage| std::mem::take(&mut storage.reload)).then_some(Exit::Reload)
+        };
+        let result = match exit {
+            Some(_) => transaction.commit(),
+            None => transaction.autosave(),
+        };
+        with(|storage| match result {
+            Ok(()) => storage.error = None,
+            Err(err) => {
+                // Only warn once, not every frame.
+                if storage.error.is_none() {
+                    warn!("could not save: {err}");
+                }
+                storage.error = Some(err.to_string());
+            }
+        });
+        if let Some(exit) = exit {
+            return exit;
+        }
+    }
+}
+
+#[test]
+fn frames_only_become_visible_when_committed() {
+    use super::{backend::Memory, Saveable};
+    use crate::datastructures::{Sensor, SetGet};
+
+    set_backend(Memory::default());
+    let mut time = Saveable::<f64>::new(1.5, "time");
+    let mut zoom = Sensor::raw(Saveable::<f32>::new(0.5, "map/zoom"));
+    let reader = zoom.make_reader();
+    {
+        let mut transaction = Transaction::start();
+        time.set(2.0);
+        zoom.set(0.25);
+        assert_eq!(reader.get(), Some(0.25));
+        transaction.commit().unwrap();
+        // Frames without changes don't write anything.
+        transaction.commit().unwrap();
+        assert!(!transaction.odd);
+        // Changes of a frame that never got committed are lost.
+        time.set(3.0);
+    }
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 2.0);
+    assert_eq!(*Saveable::<f32>::new(0.0, "map/zoom"), 0.25);
+
+    // Both buffers stay complete, even though each frame only writes what changed.
+    {
+        let mut transaction = Transaction::start();
+        time.set(4.0);
+        transaction.commit().unwrap();
+        assert!(transaction.odd);
+    }
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 4.0);
+    assert_eq!(*Saveable::<f32>::new(0.0, "map/zoom"), 0.25);
+    with(|storage| {
+        let buffer = |key| storage.backend.get(&format!("slots/default/{key}"));
+        assert_eq!(buffer("1/map/zoom").as_deref(), Some("0.25"));
+        assert_eq!(buffer("0/time").as_deref(), Some("2"));
+    });
+}
+
+#[test]
+fn recover_from_corruption_and_failed_writes() {
+    use super::{backend::Memory, Saveable};
+    use crate::datastructures::SetGet;
+
+    /// Fails all writes while `full` is set, like a full disk.
+    struct Full {
+        memory: Memory,
+        full: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl StorageBackend for Full {
+        fn get(&self, key: &str) -> Option<String> {
+            self.memory.get(key)
+        }
+
+        fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+            if self.full.get() {
+                return Err(io::Error::other("disk full"));
+            }
+            self.memory.set(key, value)
+        }
+
+        fn remove(&mut self, key: &str) -> io::Result<()> {
+            self.memory.remove(key)
+        }
+
+        fn keys(&self, prefix: &str) -> Vec<String> {
+            self.memory.keys(prefix)
+        }
+    }
+
+    let full = std::rc::Rc::new(std::cell::Cell::new(false));
+    set_backend(Full {
+        memory: Memory::default(),
+        full: full.clone(),
+    });
+    let mut time = Saveable::<f64>::new(0.0, "time");
+    {
+        let mut transaction = Transaction::start();
+        time.set(1.0);
+        transaction.commit().unwrap();
+        time.set(2.0);
+        transaction.commit().unwrap();
+        full.set(true);
+        time.set(3.0);
+        assert!(transaction.commit().is_err());
+        assert!(transaction.odd);
+        // The failed frame gets written together with the next one.
+        full.set(false);
+        transaction.commit().unwrap();
+        assert!(!transaction.odd);
+    }
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 3.0);
+
+    // A corrupt value falls back to the frame before, a corrupt marker to the newer frame.
+    with(|storage| {
+        let backend = &mut storage.backend;
+        backend.set("slots/default/0/time", "three").unwrap();
+        backend.set("slots/default/odd", "maybe").unwrap();
+    });
+    assert_eq!(*Saveable::<f64>::new(0.0, "time"), 2.0);
+}
+
+#[test]
+fn only_write_what_changed() {
+    use super::{backend::Memory, ComplexSaveable};
+
+    set_backend(Memory::default());
+    let mut list = ComplexSaveable::<Vec<u8>>::default("list");
+    let mut transaction = Transaction::start();
+    list.update(|list| list.extend([1, 2, 3]));
+    transaction.commit().unwrap();
+    let odd = transaction.odd;
+    // Saving the whole list again changes nothing.
+    list.update(|_| {});
+    transaction.commit().unwrap();
+    assert_eq!(transaction.odd, odd);
+    list.update(|list| list[1] = 5);
+    transaction.commit().unwrap();
+    assert_eq!(transaction.odd, !odd);
+    let changed: Vec<_> = transaction.previous.keys().map(String::as_str).collect();
+    assert_eq!(changed, ["list/1", slots::LAST_PLAYED, slots::PLAY_TIME]);
+    list.update(|list| list.truncate(1));
+    transaction.commit().unwrap();
+    let changed: Vec<_> = transaction.previous.iter().take(3).collect();
+    assert_eq!(
+        changed,
+        [
+            (&"list/1".to_owned(), &None),
+            (&"list/2".to_owned(), &None),
+            (&"list/len".to_owned(), &Some("1".to_owned())),
+        ]
+    );
+    drop(transaction);
+    assert_eq!(**ComplexSaveable::<Vec<u8>>::default("list"), [1]);
+}
+
+#[test]
+fn autosave_waits_for_interval_or_checkpoint() {
+    use super::{backend::Memory, Saveable};
+    use crate::datastructures::SetGet;
+
+    set_backend(Memory::default());
+    set_autosave_interval(f64::INFINITY);
+    let mut time = Saveable::<f64>::new(0.0, "time");
+    let mut transaction = Transaction::start();
+    let odd = transaction.odd;
+    time.set(1.0);
+    transaction.autosave().unwrap();
+    assert_eq!(transaction.odd, odd);
+    checkpoint();
+    transaction.autosave().unwrap();
+    assert_eq!(transaction.odd, !odd);
+    // The checkpoint only applies to one commit.
+    time.set(2.0);
+    transaction.autosave().unwrap();
+    assert_eq!(transaction.odd, !odd);
+}
+
+#[test]
+fn snapshot_every_hour_of_play_time() {
+    use super::backend::Memory;
+
+    // Almost an hour played already.
+    let mut backend = Memory::default();
+    backend.set("slots/default/odd", "true").unwrap();
+    let played = format!("slots/default/1/{}", slots::PLAY_TIME);
+    backend.set(&played, &(HOUR - 0.01).to_string()).unwrap();
+    set_backend(backend);
+    let mut transaction = Transaction::start();
+    set("time", "1");
+    transaction.commit().unwrap();
+    assert_eq!(history::list(), []);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    set("time", "2");
+    transaction.commit().unwrap();
+    let snapshots = history::list();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].kind, SnapshotKind::Hourly);
+    assert_eq!(snapshots[0].get("time").as_deref(), Some("2"));
+    // Only once per hour.
+    set("time", "3");
+    transaction.commit().unwrap();
+    assert_eq!(history::list().len(), 1);
+}
+
+#[test]
+fn read_only_writes_nothing() {
+    use super::backend::Memory;
+
+    let mut backend = Memory::default();
+    for (key, value) in [
+        ("odd", "false"),
+        ("0/time", "1"),
+        ("slots/other/odd", "false"),
+        ("slots/other/0/map_zoom", "0.25"),
+    ] {
+        backend.set(key, value).unwrap();
     }
+    let keys = |backend: &dyn StorageBackend| {
+        let mut keys = backend.keys("");
+        keys.sort();
+        keys
+    };
+    let stored = keys(&backend);
+    set_read_only_backend(backend);
+    slots::select("other").unwrap();
+    // Not migrated to `ship/parts/0/0/4/zoom`.
+    assert_eq!(get("map_zoom").as_deref(), Some("0.25"));
+    let mut transaction = Transaction::start();
+    set("time", "2");
+    assert!(transaction.commit().is_err());
+    drop(transaction);
+    with(|storage| assert_eq!(keys(&*storage.backend), stored));
 }
@@ -3,6 +3,9 @@ use std::{collections::HashMap, f32::consts::PI};
 use hex2d::Spacing;
 use macroquad::prelude::*;
 
+use crate::save::ComplexSaveable;
+
+mod layout;
 mod segment;
 mod segments {
     mod gauge;
@@ -17,12 +20,16 @@ mod attachements {
 }
 
 pub use attachements::*;
+pub use layout::*;
 pub use segment::*;
 pub use segments::*;
 
 pub(crate) struct SpaceShip {
     pub(crate) pos: Vec2,
     pub(crate) grid: HashMap<hex2d::Coordinate, Segment>,
+    /// What `grid` was built from.
+    layout: ComplexSaveable<Vec<SegmentLayout>>,
+    layout_saved: bool,
 }
 
 // sqrt is not const fn, so we inline sqrt(3)
@@ -31,6 +38,11 @@ pub(crate) const SPACING: Spacing = Spacing::FlatTop(segment::SIZE / SQRT3);
 
 impl SpaceShip {
     pub(crate) fn update(&mut self) {
+        // The ship of a new game only exists in code, until its layout gets saved with the first frame.
+        if !self.layout_saved {
+            self.layout.update(|_| {});
+            self.layout_saved = true;
+        }
         for (pos, segment) in self.grid.iter_mut() {
             let (x, y) = pos.to_pixel(SPACING);
             segment.update(self.pos + vec2(x, y));
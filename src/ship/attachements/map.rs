@@ -2,16 +2,35 @@ use std::f32::consts::FRAC_PI_2;
 
 use macroquad::prelude::*;
 
-use crate::{datastructures::SetGet, save::Saveable};
+use crate::{
+    datastructures::{Sensor, SetGet},
+    save::Saveable,
+};
 
 pub struct Map {
     pub texture: Texture2D,
     pub zoom: Saveable<f32>,
     pub small_zoom: f32,
+    /// Where the map was drawn, so clicks can be translated into map coordinates.
+    pub area: Sensor<Rect>,
+}
+
+impl Map {
+    fn area(&self, mut pos: Vec2, angle: f32) -> Rect {
+        let (y, x) = (angle - FRAC_PI_2).sin_cos();
+        let zoom = self.zoom.get() * self.small_zoom;
+        pos += vec2(x, y) * 1024.0 / 2.0 * zoom;
+        pos -= vec2(self.texture.width(), self.texture.height()) / 2.0 * zoom;
+        let size = vec2(1024.0, 1024.0) * zoom;
+        Rect::new(pos.x, pos.y, size.x, size.y)
+    }
 }
 
 impl crate::ship::Attachement for Map {
-    fn update(&mut self, _pos: macroquad::prelude::Vec2, _angle: f32) {}
+    fn update(&mut self, pos: macroquad::prelude::Vec2, angle: f32) {
+        let area = self.area(pos, angle);
+        self.area.set(area);
+    }
 
     fn control(&mut self, dir: Option<bool>, x: Option<f32>) {
         if let Some(dir) = dir {
@@ -27,18 +46,15 @@ impl crate::ship::Attachement for Map {
         }
     }
 
-    fn draw(&self, mut pos: macroquad::prelude::Vec2, angle: f32) {
-        let (y, x) = (angle - FRAC_PI_2).sin_cos();
-        let zoom = self.zoom.get() * self.small_zoom;
-        pos += vec2(x, y) * 1024.0 / 2.0 * zoom;
-        pos -= vec2(self.texture.width(), self.texture.height()) / 2.0 * zoom;
+    fn draw(&self, pos: macroquad::prelude::Vec2, angle: f32) {
+        let area = self.area(pos, angle);
         draw_texture_ex(
             &self.texture,
-            pos.x,
-            pos.y,
+            area.x,
+            area.y,
             WHITE,
             DrawTextureParams {
-                dest_size: Some(vec2(1024.0, 1024.0) * zoom),
+                dest_size: Some(area.size()),
                 ..DrawTextureParams::default()
             },
         );
@@ -5,6 +5,7 @@ use macroquad::prelude::*;
 use crate::{
     controlled::ControlledRange,
     datastructures::{Reader, Sensor, SetGet},
+    save::Saveable,
     ship::SIZE,
 };
 
@@ -16,10 +17,14 @@ pub(crate) struct Sail {
     rope_positions: Sensor<(Vec2, Vec2)>,
     /// When the sail moves due to different rope lengths, this is all that actually changes.
     /// 0.0 is straight up.
-    current_angle: Sensor<f32>,
+    current_angle: Sensor<Saveable<f32>>,
     pub current_angular_velocity: f32,
+    /// Only updated when `current_angular_velocity` changed noticeably, as it changes every frame.
+    saved_angular_velocity: Saveable<f32>,
     /// The force with which the sail pulls.
     force: Sensor<f32>,
+    /// Fraction of sunlight reaching the sail. There is no force in the shadow of the moon.
+    sunlight: Reader<f32>,
 
     /// Thickness of the helper lines showing what is being controlled
     helper_line: f32,
@@ -28,36 +33,48 @@ pub(crate) struct Sail {
 
 const SIDE: f32 = SIZE / 8.0;
 
+/// Smaller changes of the angular velocity are not saved, and smaller velocities stop the sail.
+const ANGULAR_VELOCITY_PRECISION: f32 = 1e-8;
+
+#[derive(Clone)]
 pub(crate) struct SailParameters {
     pub rope_positions: Reader<(Vec2, Vec2)>,
     pub force: Reader<f32>,
-    pub current_angle: Reader<f32>,
-    pub left_rope: Reader<f32>,
-    pub right_rope: Reader<f32>,
+    pub current_angle: Reader<Saveable<f32>>,
+    pub left_rope: Reader<Saveable<f32>>,
+    pub right_rope: Reader<Saveable<f32>>,
 }
 
 impl Sail {
+    /// The rope lengths, the sail width and the angle of the sail are saved below `key`.
     pub(crate) fn new(
         left_rope: f32,
         right_rope: f32,
         sail_width: f32,
         min_sail_width: f32,
         current_angle: f32,
+        sunlight: Reader<f32>,
+        key: &str,
     ) -> (Self, SailParameters) {
-        let (sail_width, _) = ControlledRange::new(min_sail_width, sail_width);
-        let (left_rope, lr) = ControlledRange::new(1.0, left_rope);
-        let (right_rope, rr) = ControlledRange::new(1.0, right_rope);
+        let (sail_width, _) =
+            ControlledRange::new(min_sail_width, sail_width, format!("{key}/sail_width"));
+        let (left_rope, lr) = ControlledRange::new(1.0, left_rope, format!("{key}/left_rope"));
+        let (right_rope, rr) = ControlledRange::new(1.0, right_rope, format!("{key}/right_rope"));
         let (rope_positions, r2) = Sensor::new(Default::default());
         let (force, f) = Sensor::new(0.0);
-        let (current_angle, cur_a) = Sensor::new(current_angle);
+        let (current_angle, cur_a) =
+            Sensor::new(Saveable::new(current_angle, format!("{key}/angle")));
+        let saved_angular_velocity = Saveable::new(0.0, format!("{key}/angular_velocity"));
         (
             Self {
                 left_rope,
                 right_rope,
                 sail_width,
                 current_angle,
-                current_angular_velocity: 0.0,
+                current_angular_velocity: *saved_angular_velocity,
+                saved_angular_velocity,
                 force,
+                sunlight,
                 rope_positions,
                 helper_line: 0.0,
                 helper_pos: None,
@@ -81,7 +98,8 @@ impl crate::ship::Attachement for Sail {
         let vec = left - right;
         let angle = vec.y.atan2(vec.x);
         let cos_angle = angle.cos();
-        let f = self.sail_width.value.get() * cos_angle * cos_angle;
+        let sunlight = self.sunlight.get().unwrap_or(1.0);
+        let f = self.sail_width.value.get() * cos_angle * cos_angle * sunlight;
 
         self.force.set(f);
 
@@ -102,8 +120,17 @@ impl crate::ship::Attachement for Sail {
         if self.current_angular_velocity.abs() * threshold < 10.0 {
             self.current_angular_velocity *= 0.95;
         }
-
+        // Otherwise the dampening would keep changing the velocity forever.
+        if self.current_angular_velocity.abs() < ANGULAR_VELOCITY_PRECISION {
+            self.current_angular_velocity = 0.0;
+        }
         let cav = self.current_angular_velocity;
+        if (cav - *self.saved_angular_velocity).abs() >= ANGULAR_VELOCITY_PRECISION
+            || (cav == 0.0 && *self.saved_angular_velocity != 0.0)
+        {
+            self.saved_angular_velocity.set(cav);
+        }
+
         let a = self.current_angle.modify(|a| a + cav);
 
         if a.abs() > std::f32::consts::FRAC_PI_6 {
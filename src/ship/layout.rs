@@ -0,0 +1,196 @@
+//! Which parts a ship is built from, so it can be rebuilt from a save game.
+//!
+//! Only the kinds of parts and where they are is part of the layout. Each part saves its own state
+//! below `ship/parts/<x>/<y>/<side>` for attachements and `ship/parts/<x>/<y>/content` for contents.
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, PI};
+
+use macroquad::prelude::*;
+
+use crate::{
+    datastructures::{Reader, Sensor},
+    save::{ComplexSave, ComplexSaveable, Save, Saveable},
+    ship::{
+        Attachement, Content, Gauge, GaugeHandle, Map, Sail, SailParameters, Segment, SpaceShip,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Save)]
+pub enum ContentKind {
+    #[default]
+    Empty,
+    /// Shows the force of the first sail and how much it changed.
+    ForceGauge,
+    /// Shows the angle of the first sail and the difference of its rope lengths.
+    AngleGauge,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Save)]
+pub enum AttachementKind {
+    #[default]
+    Empty,
+    Sail,
+    Map,
+}
+
+#[derive(Default, Save)]
+pub struct SegmentLayout {
+    pub x: i32,
+    pub y: i32,
+    pub content: ContentKind,
+    pub attachements: ComplexSave<[AttachementKind; 6]>,
+}
+
+/// The ship of a new game.
+fn start() -> Vec<SegmentLayout> {
+    let mut attachements = [AttachementKind::Empty; 6];
+    attachements[1] = AttachementKind::Sail;
+    attachements[4] = AttachementKind::Map;
+    vec![
+        SegmentLayout {
+            x: 0,
+            y: 0,
+            content: ContentKind::ForceGauge,
+            attachements: attachements.into(),
+        },
+        SegmentLayout {
+            x: 0,
+            y: -1,
+            content: ContentKind::AngleGauge,
+            attachements: Default::default(),
+        },
+    ]
+}
+
+/// Where the part on `side` of a segment saves its state.
+fn key(layout: &SegmentLayout, side: usize) -> String {
+    format!("ship/parts/{}/{}/{side}", layout.x, layout.y)
+}
+
+/// Everything the rest of the game needs to know about the parts of a ship.
+pub(crate) struct Parts {
+    pub sails: Vec<SailParameters>,
+    /// Where each map was drawn.
+    pub map_areas: Vec<Reader<Rect>>,
+}
+
+const SAIL_WIDTH: f32 = 100.0;
+
+fn attachement(
+    kind: AttachementKind,
+    key: &str,
+    map: &Texture2D,
+    sunlight: &Reader<f32>,
+    parts: &mut Parts,
+) -> Option<Box<dyn Attachement>> {
+    Some(match kind {
+        AttachementKind::Empty => return None,
+        AttachementKind::Sail => {
+            let (sail, parameters) = Sail::new(
+                200.0,
+                200.0,
+                SAIL_WIDTH,
+                20.0,
+                -FRAC_PI_3,
+                sunlight.clone(),
+                key,
+            );
+            parts.sails.push(parameters);
+            Box::new(sail)
+        }
+        AttachementKind::Map => {
+            let (area, reader) = Sensor::new(Rect::default());
+            parts.map_areas.push(reader);
+            Box::new(Map {
+                texture: map.clone(),
+                zoom: Saveable::new(0.5, format!("{key}/zoom")),
+                small_zoom: 1.0,
+                area,
+            })
+        }
+    })
+}
+
+/// Gauges show what the first sail of the ship is doing, if there is one.
+fn content(
+    kind: ContentKind,
+    key: &str,
+    sail: Option<&SailParameters>,
+) -> Option<Box<dyn Content>> {
+    let sail = || sail.cloned();
+    Some(match kind {
+        ContentKind::Empty => return None,
+        ContentKind::ForceGauge => {
+            let force = sail();
+            Box::new(Gauge::new(
+                [
+                    GaugeHandle::from(move |_| force.as_ref()?.force.get()),
+                    GaugeHandle::from(move |diff| Some(diff * 100.0_f32)).relative(),
+                ],
+                0.0..=SAIL_WIDTH,
+                (-FRAC_PI_3 * 2.0)..=(FRAC_PI_3 * 2.0),
+                key,
+            ))
+        }
+        ContentKind::AngleGauge => {
+            let (angle, ropes) = (sail(), sail());
+            Box::new(Gauge::new(
+                [
+                    GaugeHandle::from(move |_| Some(-angle.as_ref()?.current_angle.get()?)),
+                    GaugeHandle::from(move |diff| Some(diff * 1000.0_f32)).relative(),
+                    GaugeHandle::from(move |_| {
+                        let ropes = ropes.as_ref()?;
+                        Some((ropes.right_rope.get()? - ropes.left_rope.get()?) / 10.0 + PI)
+                    }),
+                ],
+                -FRAC_PI_2..=FRAC_PI_2,
+                -FRAC_PI_2..=FRAC_PI_2,
+                key,
+            ))
+        }
+    })
+}
+
+impl SpaceShip {
+    /// Build the saved ship, or the ship of a new game if nothing was saved yet.
+    /// Maps show `map`, and sails get pushed by the fraction of `sunlight` reaching them.
+    pub(crate) fn load(map: &Texture2D, sunlight: Reader<f32>) -> (Self, Parts) {
+        let layout: ComplexSaveable<Vec<SegmentLayout>> = Saveable::new(start(), "ship/layout");
+        let mut parts = Parts {
+            sails: Vec::new(),
+            map_areas: Vec::new(),
+        };
+        // Attachements first, so the contents can show what the attachements are doing.
+        let attachements: Vec<[_; 6]> = layout
+            .iter()
+            .map(|segment| {
+                std::array::from_fn(|side| {
+                    let key = key(segment, side);
+                    attachement(segment.attachements[side], &key, map, &sunlight, &mut parts)
+                })
+            })
+            .collect();
+        let grid = layout
+            .iter()
+            .zip(attachements)
+            .map(|(segment, attachements)| {
+                let key = format!("ship/parts/{}/{}/content", segment.x, segment.y);
+                let content = content(segment.content, &key, parts.sails.first());
+                (
+                    (segment.x, segment.y).into(),
+                    Segment {
+                        content,
+                        attachements,
+                    },
+                )
+            })
+            .collect();
+        let ship = SpaceShip {
+            pos: Vec2::new(0.0, 0.0),
+            grid,
+            layout,
+            layout_saved: false,
+        };
+        (ship, parts)
+    }
+}
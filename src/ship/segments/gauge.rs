@@ -2,9 +2,12 @@ use std::ops::RangeInclusive;
 
 use macroquad::prelude::Vec2;
 
-use crate::ship::{
-    segment::{Content, Element},
-    SIZE,
+use crate::{
+    save::{ComplexSaveable, Saveable},
+    ship::{
+        segment::{Content, Element},
+        SIZE,
+    },
 };
 
 pub enum GaugeHandleKind {
@@ -39,24 +42,38 @@ impl GaugeHandle {
 pub struct Gauge {
     pub data_sources: Vec<GaugeHandle>,
     pub data: Vec<f32>,
+    /// Only updated when `data` changed noticeably, as it changes every frame while the ship moves.
+    saved: ComplexSaveable<Vec<f32>>,
     pub value_range: RangeInclusive<f32>,
     pub handle_range: RangeInclusive<f32>,
 }
 
+/// Smaller changes of the shown values, relative to the value range, are not saved.
+const PRECISION: f32 = 0.001;
+
 impl Gauge {
+    /// The shown values are saved at `key`.
     pub fn new(
         data_sources: impl IntoIterator<Item = GaugeHandle>,
         value_range: RangeInclusive<f32>,
         handle_range: RangeInclusive<f32>,
+        key: impl ToString,
     ) -> Self {
         let mut data_sources: Vec<_> = data_sources.into_iter().collect();
-        let data = data_sources
-            .iter_mut()
-            .map(|gh| (gh.source)(0.0).unwrap_or(*value_range.start()))
-            .collect();
+        let saved: ComplexSaveable<Vec<f32>> = Saveable::default(key);
+        // The sources may have changed since the values were saved.
+        let data = if saved.len() == data_sources.len() {
+            saved.to_vec()
+        } else {
+            data_sources
+                .iter_mut()
+                .map(|gh| (gh.source)(0.0).unwrap_or(*value_range.start()))
+                .collect()
+        };
         Self {
             data_sources,
             data,
+            saved,
             value_range,
             handle_range,
         }
@@ -72,6 +89,15 @@ impl Element for Gauge {
                 *dest = new;
             }
         }
+        let precision = (self.value_range.end() - self.value_range.start()) * PRECISION;
+        let changed = self.saved.len() != self.data.len()
+            || self.saved.iter().zip(&self.data).any(|(saved, new)| {
+                (saved - new).abs() >= precision || (*new == 0.0) != (*saved == 0.0)
+            });
+        if changed {
+            let data = &self.data;
+            self.saved.update(|saved| (**saved).clone_from(data));
+        }
     }
 
     fn draw(&self, pos: Vec2) {
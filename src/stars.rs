@@ -17,6 +17,8 @@ pub struct Stars {
     positions: Vec<Star>,
     photons: Vec<Photon>,
     pub sails: Vec<Reader<(Vec2, Vec2)>>,
+    /// Fraction of sunlight reaching the ship. No new photons arrive in the shadow of the moon.
+    pub sunlight: Option<Reader<f32>>,
     w: f32,
     h: f32,
     // Maximum sizes of the window over the runtime of the program
@@ -63,7 +65,20 @@ impl Stars {
         rect.y -= SPEED;
         rect.w += SPEED;
         rect.h += SPEED;
+        let sunlight = self
+            .sunlight
+            .as_ref()
+            .and_then(|sunlight| sunlight.get())
+            .unwrap_or(1.0);
         for photon in &mut self.photons {
+            if !rect.contains(photon.pos) {
+                // Wait outside the screen until the sun shines again.
+                if f32::gen_range(0.0, 1.0) >= sunlight {
+                    continue;
+                }
+                photon.pos = vec2(f32::gen_range(rect.left(), rect.right()), rect.bottom());
+                photon.dir = vec2(0.0, -SPEED);
+            }
             let len_vec = photon.dir.normalize() * LENGTH;
             for sail in &self.sails {
                 let (l, r) = sail.get().unwrap();
@@ -74,10 +89,6 @@ impl Stars {
                 }
             }
             photon.pos += photon.dir;
-            if !rect.contains(photon.pos) {
-                photon.pos = vec2(f32::gen_range(rect.left(), rect.right()), rect.bottom());
-                photon.dir = vec2(0.0, -SPEED);
-            }
         }
     }
     pub fn draw(&self) {